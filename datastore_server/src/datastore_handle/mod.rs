@@ -1,54 +1,209 @@
-use std::{net::UdpSocket, fs::{File, remove_file}};
-use crate::{MTU, Serr, protocol::{create_pkt, BODY_LEN, SYNACK, send::send, ACK, receive::receive, get_seq, send_buf}, FLAGS_LEN, SEQ_LEN};
+use std::{fs::{File, Metadata, remove_file, symlink_metadata}, io::{Cursor, ErrorKind, Read, Seek, SeekFrom}, path::Path, time::UNIX_EPOCH};
+use protocol::{connection::Connection, storage::{Upload, Resumable, file_digest, forget_digest}, listing, digest::DIGEST_LEN, info, create_pkt, stat_body, synack_body, get_patch, get_digest, Span, Serr, SYNACK, ACK, create_header, get_seq, get_mtu, send_buf, linger, send::send, receive::receive};
 
 
-/// Handle managing the result of a result.
-fn handle_result<T, E>(serr: Serr, r: Result<T, E>, socket: &UdpSocket) -> Result<T, Serr> {
-  match r {
-    Ok(i) => Result::Ok(i),
-    Err(_) => Result::Err(serr),
+/// Process a GET request on the connection, sending the requested span of the file.
+pub fn handle_get(filename: &str, mut file: File, file_size: u64, span: Span, conn: &mut Connection) -> Result<(), Serr> {
+  let (offset, length): (u64, u64) = match span.within(file_size) {
+    Some(s) => s,
+    None => return Err(Serr::RANGE(format!("{:?} is outside of {} of {} bytes", span, filename, file_size))),
+  };
+
+  // only the span is read from the file
+  if file.seek(SeekFrom::Start(offset)).is_err() {
+    return Err(Serr::SERVER(format!("Unable to seek to byte {} of {}", offset, filename)));
   }
+  handle_send(filename, file.take(length), offset, length, file_size, conn)
 }
 
 
-/// Process a GET request.
-pub fn handle_get(filename: String, file: File, file_size: u64, socket: &UdpSocket) -> Result<(), Serr> {
-  let buf: [u8; MTU];
-  let data: [u8; BODY_LEN] = [0; BODY_LEN];
+/// Send the provided source, holding the span of the provided length
+/// starting at the provided offset of a file of the provided size.
+fn handle_send<R: Read>(filename: &str, file: R, offset: u64, length: u64, file_size: u64, conn: &mut Connection) -> Result<(), Serr> {
+  let mut buf: Vec<u8>;
+
+  // send span len (syn & ack) until ack w falgs = 128 (ack),
+  // echoing the connection ID chosen by the proxy
+  buf = create_pkt(SYNACK, conn.id, length, &synack_body(offset, file_size));
+  buf = send_buf(conn, &buf, ACK, filename)?;
 
-  // send file len (syn & ack) until ack w falgs = 128 (ack)
-  buf = create_pkt(SYNACK, file_size, &data);
-  send_buf(socket, &buf, ACK)?;
+  // the ack carries the largest datagram the proxy accepts
+  conn.negotiate(get_mtu(&buf)?);
 
   // call send
-  send(socket, file, filename, file_size)
+  send(conn, file, filename)
 }
 
 
-/// Process a POST request.
-pub fn handle_post(filename: String, socket: &UdpSocket, buf: &[u8; MTU]) -> Result<(), Serr> {
-  let size: u64 = get_seq(&buf)?;
+/// Process a POST request on the connection, replacing the file at the
+/// provided path once it was received in full.
+pub fn handle_post(filename: &str, path: &Path, conn: &mut Connection, buf: &[u8]) -> Result<(), Serr> {
+  let size: u64 = get_seq(buf)?;
+  let upload: Upload = match Upload::create(path) {
+    Ok(u) => u,
+    Err(_) => return Err(Serr::SERVER(format!("Unable to open {}", filename))),
+  };
 
-  // call receive
-  match receive(socket, filename.clone(), size) {
-    Ok(_) => {
-      println!("Succsefully received {}", filename);
-      Ok(())
-    },
-    Err(e) => {
-      remove_file(&filename);
-      Err(e)
-    }
+  // call receive, an unfinished upload leaves the previous version in place
+  receive(conn, upload, filename, size)?;
+  info!("Succsefully received {}", filename);
+  Ok(())
+}
+
+
+/// Process a DELETE request on the connection, removing the file at the provided path.
+pub fn handle_delete(filename: &str, path: &Path, conn: &mut Connection) -> Result<(), Serr> {
+  // a directory isn't a file to delete, a symlink is removed itself
+  match symlink_metadata(path) {
+    Ok(m) if m.is_file() || m.is_symlink() => (),
+    Ok(_) => return Err(Serr::DNE(format!("{} is not a file", filename))),
+    Err(_) => return Err(Serr::DNE(format!("{} does not exist", filename))),
+  }
+
+  match remove_file(path) {
+    Ok(_) => forget_digest(path),
+    Err(e) if e.kind() == ErrorKind::NotFound => return Err(Serr::DNE(format!("{} does not exist", filename))),
+    Err(e) => return Err(Serr::SERVER(format!("Unable to delete {}:\n{}", filename, e))),
+  }
+  info!("Successfully deleted {}", filename);
+
+  // ack the request, answering it again if the ack is lost
+  let ack: Vec<u8> = create_header(ACK, conn.id, 0);
+  linger(conn, &ack);
+  Ok(())
+}
+
+
+/// Process a STAT request on the connection, answering with the size,
+/// modification time and digest of the file at the provided path.
+///
+/// The request is answered once, a retransmitted request is answered anew.
+pub fn handle_stat(filename: &str, path: &Path, conn: &Connection) -> Result<(), Serr> {
+  let mut file: File = match File::open(path) {
+    Ok(f) => f,
+    Err(_) => return Err(Serr::DNE(format!("{} does not exist", filename))),
+  };
+  let metadata: Metadata = match file.metadata() {
+    Ok(m) if m.is_file() => m,
+    _ => return Err(Serr::DNE(format!("{} is not a file", filename))),
+  };
+
+  let mtime: u64 = metadata.modified().ok()
+    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+    .map_or(0, |d| d.as_secs());
+  let digest: [u8; DIGEST_LEN] = match file_digest(path, &mut file) {
+    Ok(d) => d,
+    Err(_) => return Err(Serr::SERVER(format!("Unable to read {}", filename))),
+  };
+
+  let _ = conn.send(&create_pkt(ACK, conn.id, metadata.len(), &stat_body(mtime, &digest)));
+  info!("Successfully answered {} STAT", filename);
+  Ok(())
+}
+
+
+/// Process a FIN outliving the upload of the file at the provided path,
+/// the receiver's FIN having been lost, by answering it again only if the
+/// file was committed with the digest of the data sent.
+pub fn handle_stale_fin(filename: &str, path: &Path, conn: &Connection, buf: &[u8]) -> Result<(), Serr> {
+  let committed: bool = File::open(path)
+    .and_then(|mut f| file_digest(path, &mut f))
+    .is_ok_and(|d| get_digest(buf).is_ok_and(|sent| sent == d));
+
+  if !committed {
+    return Err(Serr::SERVER(format!("{} was not committed with the data of a stale FIN", filename)));
   }
+  let _ = conn.send(buf);
+  info!("Answered stale FIN for {}", filename);
+  Ok(())
+}
+
+
+
+/// Process a LIST request on the connection, sending the entries of the
+/// directory at the provided path as a GET sends a whole file.
+pub fn handle_list(filename: &str, path: &Path, conn: &mut Connection) -> Result<(), Serr> {
+  let entries: Vec<u8> = match listing::list(path) {
+    Ok(e) => listing::encode(&e),
+    Err(e) if e.kind() == ErrorKind::NotFound || e.kind() == ErrorKind::NotADirectory => {
+      return Err(Serr::DNE(format!("{} is not a directory", filename)));
+    },
+    Err(e) => return Err(Serr::SERVER(format!("Unable to list {}:\n{}", filename, e))),
+  };
+
+  let size: u64 = entries.len() as u64;
+  handle_send(filename, Cursor::new(entries), 0, size, size, conn)
+}
+
+
+/// Process a CREATE request on the connection, starting a resumable
+/// upload to the provided path and answering with its ID.
+pub fn handle_create(filename: &str, path: &Path, conn: &mut Connection, buf: &[u8]) -> Result<(), Serr> {
+  let length: u64 = get_seq(buf)?;
+  let id: u64 = match Resumable::create(path, length) {
+    Ok(i) => i,
+    Err(e) => return Err(Serr::SERVER(format!("Unable to start an upload of {}:\n{}", filename, e))),
+  };
+  info!("Started upload {:016x} of {} bytes to {}", id, length, filename);
+
+  // ack the request with the ID, answering it again if the ack is lost,
+  // so a retransmitted request doesn't start another upload
+  let ack: Vec<u8> = create_header(ACK, conn.id, id);
+  linger(conn, &ack);
+  Ok(())
+}
+
+
+/// Process an OFFSET request on the connection, answering with the number
+/// of bytes received so far of the resumable upload to the provided path,
+/// and the size of the whole file.
+///
+/// The request is answered once, a retransmitted request is answered anew.
+pub fn handle_offset(filename: &str, path: &Path, conn: &Connection, buf: &[u8]) -> Result<(), Serr> {
+  let id: u64 = get_seq(buf)?;
+  let (offset, length) = match Resumable::status(path, id) {
+    Ok(s) => s,
+    Err(_) => return Err(Serr::DNE(format!("no upload {:016x} of {}", id, filename))),
+  };
+
+  let _ = conn.send(&create_pkt(ACK, conn.id, offset, &length.to_be_bytes()));
+  info!("Successfully answered {} OFFSET", filename);
+  Ok(())
+}
+
+
+/// Process an ABORT request on the connection, abandoning the resumable
+/// upload to the provided path it names.
+pub fn handle_abort(filename: &str, path: &Path, conn: &mut Connection, buf: &[u8]) -> Result<(), Serr> {
+  let id: u64 = get_seq(buf)?;
+  Resumable::remove(path, id)?;
+  info!("Abandoned upload {:016x} of {}", id, filename);
+
+  // ack the request, answering it again if the ack is lost
+  let ack: Vec<u8> = create_header(ACK, conn.id, id);
+  linger(conn, &ack);
+  Ok(())
 }
 
 
-/// Creates a string from a designated slice of a byte buffer.
-/// 
-/// Inclusive start, exclusive end.
-pub fn bytes_to_str(buf: &[u8], start: usize, end: usize) -> String {
-  buf[start..end]
-    .into_iter()
-    .map(|b| *b as char)
-    .collect::<String>()
+/// Process a PATCH request on the connection, appending the data received
+/// to the resumable upload to the provided path.
+///
+/// Data received before the transfer fails is kept, so the upload
+/// can be continued from where it left off.
+pub fn handle_patch(filename: &str, path: &Path, conn: &mut Connection, buf: &[u8]) -> Result<(), Serr> {
+  let size: u64 = get_seq(buf)?;
+  let (id, offset) = get_patch(buf)?;
+  let upload: Resumable = Resumable::resume(path, id, offset, size)?;
+  let length: u64 = upload.length();
+
+  receive(conn, upload, filename, size)?;
+  info!("Successfully received {} bytes of upload {:016x} to {}", size, id, filename);
+
+  // the digest of a completed upload is kept once the transfer is over,
+  // as reading the whole file would hold up its last FIN
+  if offset + size == length {
+    let _ = File::open(path).and_then(|mut f| file_digest(path, &mut f));
+  }
+  Ok(())
 }
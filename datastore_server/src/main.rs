@@ -1,38 +1,25 @@
+#![allow(clippy::upper_case_acronyms, clippy::needless_late_init)]
+
 mod datastore_handle;
-mod protocol;
+mod session;
 
-use std::{net::{UdpSocket, SocketAddr}, fs::File};
+use std::{net::{UdpSocket, SocketAddr, IpAddr, Ipv4Addr, Ipv6Addr}, fs::File, collections::HashMap, sync::mpsc::Sender, thread::{self, JoinHandle}};
 use datastore_handle::*;
-use protocol::{SLEEP_TIME, create_pkt, FLAG_404, FLAG_500, get_seq, ACK};
-
-use crate::protocol::{create_header, FIN};
-
-/// ASCII value for a carriage return
-const CR: u8 = 13;
-
-/// Minimum Ethernet MTU in bytes
-const ETHER_MTU: usize = 1500;
-
-/// UDP header length in bytes
-const UDP_H_LEN: usize = 8;
+use session::Session;
+use protocol::{Channel, DATASTORE_PORT, MAX_MTU, connection::{Connection, Limits}, config::{Config, LIMIT_KEYS}, storage::Root, log, info, error, HEADER_LEN, FIN, FLAG_400, FLAG_403, FLAG_404, FLAG_409, FLAG_416, FLAG_500, Serr, create_header, get_seq, get_conn_id, get_filename, get_span};
 
-/// IP header length in bytes
-const IP_H_LEN: usize = 20;
 
-/// Minimum MTU in bytes
-const MTU: usize = ETHER_MTU - UDP_H_LEN - IP_H_LEN;
+/// Usage of the datastore
+const USAGE: &str = "usage: datastore_server [--config <file>] [--bind <address>] [--port <port>] [--root <directory>]
+                        [--retries <count>] [--deadline <seconds>] [--timeout <milliseconds>]
+                        [--window <packets>] [--mtu <bytes>] [--log <off|error|warn|info|debug>]";
 
-/// Length of flags field in bytes
-const FLAGS_LEN: usize = 1;
+/// Keys configuring the datastore, besides the limits of transfers
+const KEYS: [&str; 3] = ["bind", "port", "root"];
 
-/// Length of sequence number field in bytes
-const SEQ_LEN: usize = 8;
 
-/// Length of body field in bytes
-const BODY_LEN: usize = MTU - FLAGS_LEN - SEQ_LEN;
-
-/// Starting byte position of body field
-const BODY_START: usize = FLAGS_LEN + SEQ_LEN;
+/// A live session's inbox and the thread handling it.
+type SessionHandle = (Sender<Vec<u8>>, JoinHandle<()>);
 
 
 /// An enumeration of supported operations between a
@@ -42,165 +29,313 @@ const BODY_START: usize = FLAGS_LEN + SEQ_LEN;
 enum Op {
   GET(String),
   POST(String),
+  DELETE(String),
+  STAT(String),
+  LIST(String),
+  CREATE(String),
+  OFFSET(String),
+  PATCH(String),
+  ABORT(String),
   FIN,
   ACK,
-  NA(u8, u64),
+  NA(u8),
 }
 
 
-/// Enum of the possible errors.
-#[derive(Debug)]
-#[derive(PartialEq)]
-pub enum ReadData {
-  MORE,
-  DONE,
-}
+/// Handle requests sent to the datastore.
+fn main() {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  if args.iter().any(|a| a == "-h" || a == "--help") {
+    println!("{}", USAGE);
+    return;
+  }
 
+  let (mut sockets, limits, root) = match configure(&args) {
+    Ok(c) => c,
+    Err(e) => {
+      eprintln!("{}\n{}", e, USAGE);
+      return;
+    }
+  };
 
-/// Enum of the possible errors.
-#[derive(Debug)]
-#[derive(PartialEq)]
-pub enum Serr {
-  DNE(String),
-  SERVER(String),
+  // each socket bound is served on its own thread, the last on this one
+  let last: Option<UdpSocket> = sockets.pop();
+  for socket in sockets {
+    let root: Root = root.clone();
+    thread::spawn(move || serve(socket, limits, root));
+  }
+  if let Some(socket) = last {
+    serve(socket, limits, root);
+  }
 }
 
 
-/// Handle requests sent to the datastore.
-fn main() {
+/// Handle requests sent to the provided socket.
+///
+/// Datagrams are demultiplexed by the address of the proxy that sent
+/// them and their connection ID, and each transfer is handled on its
+/// own thread.
+fn serve(socket: UdpSocket, limits: Limits, root: Root) {
+  let mut sessions: HashMap<(SocketAddr, u32), SessionHandle> = HashMap::new();
+  let mut buf: [u8; MAX_MTU];
+  let mut length: usize;
+  let mut addr: SocketAddr;
+  let mut id: u32;
+
   // receive and handle connections
   loop {
-    let socket = match UdpSocket::bind("0.0.0.0:41000") {
-      Ok(s) => s,
-      Err(_) => {
-        eprintln!("Unable to bind a UDP socket to address");
-        return;
-      }
+    buf = [0; MAX_MTU];
+    (length, addr) = match socket.recv_from(&mut buf) {
+      Ok(r) => r,
+      Err(_) => continue,
     };
 
-    handle_error(&socket, receive_connections(&socket));
+    // drop datagrams too short to belong to a connection
+    if length < HEADER_LEN { continue; }
+    let pkt: Vec<u8> = buf[..length].to_vec();
+    id = match get_conn_id(&pkt) {
+      Ok(i) => i,
+      Err(_) => continue,
+    };
+
+    // hand datagram to the live session with its sender,
+    // if the session finished the datagram starts a new one
+    if let Some((tx, _)) = sessions.get(&(addr, id)) {
+      if tx.send(pkt.clone()).is_ok() { continue; }
+    }
+
+    // clean up finished sessions and start a new one
+    sessions.retain(|_, (_, handle)| !handle.is_finished());
+    let session_socket: UdpSocket = match socket.try_clone() {
+      Ok(s) => s,
+      Err(_) => { error!("Unable to share socket with session for {}", addr); continue; },
+    };
+    let (tx, session) = Session::new(session_socket, addr);
+    let root: Root = root.clone();
+    let handle = thread::spawn(move || {
+      handle_error(&session, id, handle_connection(&session, id, limits, &root, length, &pkt));
+    });
+    sessions.insert((addr, id), (tx, handle));
   }
 }
 
 
-fn get_filename(buf: &[u8; MTU]) -> Result<String, Serr> {
-  let i: usize = match buf.iter().position(|&x| x == CR) {
-    Some(i) => i,
-    None => return Err(Serr::SERVER(format!("Cannot determine filename"))),
+/// Read the datastore's config from the command line arguments,
+/// binding its sockets.
+///
+/// Returns the sockets, the limits of transfers and the storage root.
+fn configure(args: &[String]) -> Result<(Vec<UdpSocket>, Limits, Root), String> {
+  let config: Config = Config::from_args("datastore", &[&KEYS[..], &LIMIT_KEYS[..]].concat(), args)?;
+  if let Some(arg) = config.positional.first() {
+    return Err(format!("unexpected argument {}", arg));
+  }
+
+  log::set_level(config.log_level()?);
+  let limits: Limits = config.limits()?;
+  let port: u16 = config.parse_or("port", DATASTORE_PORT)?;
+
+  let dir: &str = config.get("root").unwrap_or(".");
+  let root: Root = match Root::new(dir) {
+    Ok(r) => r,
+    Err(e) => return Err(format!("Unable to use {} as the storage root:\n{}", dir, e)),
+  };
+
+  let sockets = match config.parse::<IpAddr>("bind")? {
+    Some(ip) => UdpSocket::bind((ip, port)).map(|s| vec![s]),
+    None => bind(port),
   };
-  
-  Ok(bytes_to_str(buf, BODY_START, i))
+  match sockets {
+    Ok(s) => Ok((s, limits, root)),
+    Err(e) => Err(format!("Unable to bind a UDP socket to address:\n{}", e)),
+  }
 }
 
+
+/// Bind the datastore's sockets to all addresses.
+///
+/// Whether the IPv6 wildcard also accepts IPv4 datagrams as mapped
+/// addresses depends on the system, so the IPv4 wildcard is bound as
+/// well unless the IPv6 socket already holds it.
+fn bind(port: u16) -> std::io::Result<Vec<UdpSocket>> {
+  match (UdpSocket::bind((Ipv6Addr::UNSPECIFIED, port)), UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))) {
+    (Ok(v6), Ok(v4)) => Ok(vec![v6, v4]),
+    (Ok(s), Err(_)) | (Err(_), Ok(s)) => Ok(vec![s]),
+    (Err(e), Err(_)) => Err(e),
+  }
+}
+
+
 /// Determines the operation to perform and the file location to perform
 /// the operation at.
-/// 
+///
 /// The expected format of the buffer is:
 /// <OP><PATH<CR><LF>
-fn determine_op(length: usize, buf: &[u8; MTU]) -> Result<Op, Serr> {
+fn determine_op(length: usize, buf: &[u8]) -> Result<Op, Serr> {
   let flags: u8 = buf[0];
 
+  if length < HEADER_LEN {
+    return Err(Serr::SERVER(format!("Received truncated packet of {} bytes", length)));
+  }
+
   match flags {
     protocol::GET => Ok(Op::GET(get_filename(buf)?)),
     protocol::POST => Ok(Op::POST(get_filename(buf)?)),
+    protocol::DELETE => Ok(Op::DELETE(get_filename(buf)?)),
+    protocol::STAT => Ok(Op::STAT(get_filename(buf)?)),
+    protocol::LIST => Ok(Op::LIST(get_filename(buf)?)),
+    protocol::CREATE => Ok(Op::CREATE(get_filename(buf)?)),
+    protocol::OFFSET => Ok(Op::OFFSET(get_filename(buf)?)),
+    protocol::PATCH => Ok(Op::PATCH(get_filename(buf)?)),
+    protocol::ABORT => Ok(Op::ABORT(get_filename(buf)?)),
     protocol::FIN => Ok(Op::FIN),
     protocol::ACK => Ok(Op::ACK),
-    _ => Ok(Op::NA(flags, get_seq(&buf)?)),
+    _ => Ok(Op::NA(flags)),
   }
 }
 
 
-fn receive_connections(socket: &UdpSocket) -> Result<(), Serr> {
-  let mut buf: [u8; MTU] = [0; MTU];
-  let mut length: usize;
-  let mut addr: SocketAddr;
-
-  loop {
-    // receive datagram
-    socket.set_read_timeout(None).expect("System doesn't support set_read_timeout. Please update rust to at least v1.4.0.");
-    (length, addr) = match socket.recv_from(&mut buf) {
-      Ok(r) => r,
-      Err(_) => { continue; },
-    };
-
-    // connect and ensure read can timeout
-    socket.set_read_timeout(Some(SLEEP_TIME)).expect("System doesn't support set_read_timeout. Please update rust to at least v1.4.0.");
-    match socket.connect(addr) {
-      Ok(()) => (),
-      Err(_) => { eprintln!("Could not connect to {}", addr); continue; },
-    }
-
-    return match determine_op(length, &buf)? {
-      Op::GET(f) => {
-        println!("Received GET request for {}", f);
-        let file: File = match File::open(&f) {
-          Ok(f) => f,
-          Err(_) => return Err(Serr::DNE(format!("{} does not exist", f))),
-        };
-        let file_size = match file.metadata() {
-          Ok(i) => i,
-          Err(_) => return Err(Serr::DNE(format!("could not fetch metadata for {}", f))),
-        };
-        handle_get(f, file, file_size.len(), &socket)
-      },
-
-      Op::POST(f) => {
-        println!("Received POST request for {}", f);
-        handle_post(f, &socket, &buf)
-      },
-
-      Op::FIN => {
-        println!("Received stale FIN for {0}\nSending FIN for {0} to clean up connection", get_filename(&buf)?);
-        socket.send(&buf);
-        Ok(())
-      },
-
-      Op::ACK => {
-        println!("Received stale ACK\nSending FIN to clean up connection");
-        socket.send(&create_header(FIN, get_seq(&buf)?));
-        Ok(())
-      },
-
-      Op::NA(flag, seq) => {
-        //Ok(())
-        Err(Serr::SERVER(format!("Invalid request initializing flag: {}", flag)))
-      },
-    };
+/// Handle a connection started by the provided datagram.
+fn handle_connection(socket: &dyn Channel, id: u32, limits: Limits, root: &Root, length: usize, buf: &[u8]) -> Result<(), Serr> {
+  let mut conn: Connection = Connection::with_limits(socket, id, limits);
+
+  match determine_op(length, buf)? {
+    Op::GET(f) => {
+      info!("Received GET request for {}", f);
+      let file: File = match File::open(root.resolve(&f)?) {
+        Ok(f) => f,
+        Err(_) => return Err(Serr::DNE(format!("{} does not exist", f))),
+      };
+      // a directory opens like a file, but fails once read
+      let file_size = match file.metadata() {
+        Ok(i) if i.is_file() => i,
+        Ok(_) => return Err(Serr::DNE(format!("{} is not a file", f))),
+        Err(_) => return Err(Serr::DNE(format!("could not fetch metadata for {}", f))),
+      };
+      handle_get(&f, file, file_size.len(), get_span(buf)?, &mut conn)
+    },
+
+    Op::POST(f) => {
+      info!("Received POST request for {}", f);
+      handle_post(&f, &root.resolve(&f)?, &mut conn, buf)
+    },
+
+    Op::DELETE(f) => {
+      info!("Received DELETE request for {}", f);
+      handle_delete(&f, &root.resolve(&f)?, &mut conn)
+    },
+
+    Op::STAT(f) => {
+      info!("Received STAT request for {}", f);
+      handle_stat(&f, &root.resolve(&f)?, &conn)
+    },
+
+    Op::LIST(f) => {
+      info!("Received LIST request for {}", f);
+      handle_list(&f, &root.resolve_dir(&f)?, &mut conn)
+    },
+
+    Op::CREATE(f) => {
+      info!("Received CREATE request for {}", f);
+      handle_create(&f, &root.resolve(&f)?, &mut conn, buf)
+    },
+
+    Op::OFFSET(f) => {
+      info!("Received OFFSET request for {}", f);
+      handle_offset(&f, &root.resolve(&f)?, &conn, buf)
+    },
+
+    Op::PATCH(f) => {
+      info!("Received PATCH request for {}", f);
+      handle_patch(&f, &root.resolve(&f)?, &mut conn, buf)
+    },
+
+    Op::ABORT(f) => {
+      info!("Received ABORT request for {}", f);
+      handle_abort(&f, &root.resolve(&f)?, &mut conn, buf)
+    },
+
+    Op::FIN => {
+      let f: String = get_filename(buf)?;
+      info!("Received stale FIN for {}", f);
+      handle_stale_fin(&f, &root.resolve(&f)?, &conn, buf)
+    },
+
+    Op::ACK => {
+      info!("Received stale ACK\nSending FIN to clean up connection");
+      let _ = socket.send(&create_header(FIN, id, get_seq(buf)?));
+      Ok(())
+    },
+
+    Op::NA(flag) => {
+      Err(Serr::SERVER(format!("Invalid request initializing flag: {}", flag)))
+    },
   }
 }
 
 
 /// Send an error to the proxy if an error occurs.
-fn handle_error(socket: &UdpSocket, r: Result<(), Serr>) {
+fn handle_error(socket: &dyn Channel, id: u32, r: Result<(), Serr>) {
   match r {
     Ok(_) => (),
-    Err(e) => send_error(socket, e),
+    Err(e) => send_error(socket, id, e),
   }
 }
 
 
 /// Send the respective error for the server error over the
-/// provided socket.
-fn send_error(socket: &UdpSocket, serr: Serr) {
+/// provided socket, on the connection with the provided ID.
+fn send_error(socket: &dyn Channel, id: u32, serr: Serr) {
   let err_msg: String = match serr {
-    Serr::DNE(e) => { send_404_error(socket); e},
-    Serr::SERVER(e) => { send_500_error(socket); e},
+    Serr::DNE(e) => { send_404_error(socket, id); e},
+    Serr::FORBIDDEN(e) => { send_403_error(socket, id); e},
+    Serr::BAD(e) => { send_400_error(socket, id); e},
+    Serr::RANGE(e) => { send_416_error(socket, id); e},
+    Serr::CONFLICT(e) => { send_409_error(socket, id); e},
+    Serr::SERVER(e) => { send_500_error(socket, id); e},
+    Serr::TIMEOUT(e) => { send_500_error(socket, id); e},
+    Serr::NA => { send_500_error(socket, id); "Unsupported request received.".to_string() },
   };
-  eprintln!("{}", err_msg);
+  error!("{}", err_msg);
+}
+
+
+/// Send a 400 Error over the provided socket.
+fn send_400_error(socket: &dyn Channel, id: u32) {
+  let buf: Vec<u8> = create_header(FLAG_400, id, 0);
+  let _ = socket.send(&buf);
+}
+
+
+/// Send a 403 Error over the provided socket.
+fn send_403_error(socket: &dyn Channel, id: u32) {
+  let buf: Vec<u8> = create_header(FLAG_403, id, 0);
+  let _ = socket.send(&buf);
 }
 
 
 /// Send a 404 Error over the provided socket.
-fn send_404_error(socket: &UdpSocket) {
-  //respond(ERROR_404, socket, "Interrupted while sending 404 response");
-  let buf: [u8; MTU] = create_pkt(FLAG_404, 0, &[0; BODY_LEN]);
-  socket.send(&buf);
+fn send_404_error(socket: &dyn Channel, id: u32) {
+  let buf: Vec<u8> = create_header(FLAG_404, id, 0);
+  let _ = socket.send(&buf);
+}
+
+
+/// Send a 409 Error over the provided socket.
+fn send_409_error(socket: &dyn Channel, id: u32) {
+  let buf: Vec<u8> = create_header(FLAG_409, id, 0);
+  let _ = socket.send(&buf);
+}
+
+
+/// Send a 416 Error over the provided socket.
+fn send_416_error(socket: &dyn Channel, id: u32) {
+  let buf: Vec<u8> = create_header(FLAG_416, id, 0);
+  let _ = socket.send(&buf);
 }
 
 
 /// Send a 500 Error over the provided socket.
-fn send_500_error(socket: &UdpSocket) {
-  //respond(ERROR_500, socket, "Interrupted while sending 500 response");
-  let buf: [u8; MTU] = create_pkt(FLAG_500, 0, &[0; BODY_LEN]);
-  socket.send(&buf);
+fn send_500_error(socket: &dyn Channel, id: u32) {
+  let buf: Vec<u8> = create_header(FLAG_500, id, 0);
+  let _ = socket.send(&buf);
 }
@@ -0,0 +1,52 @@
+use std::{net::{UdpSocket, SocketAddr}, sync::mpsc::{Receiver, Sender, channel, RecvTimeoutError}, io::{self, ErrorKind}, cell::Cell, time::Duration};
+
+use protocol::{Channel, SLEEP_TIME};
+
+
+/// A transfer with a single proxy.
+///
+/// The datastore's socket is shared between all sessions, so datagrams
+/// from the peer are handed to the session by the thread reading the
+/// socket, while replies are sent straight to the peer.
+pub struct Session {
+  socket: UdpSocket,
+  addr: SocketAddr,
+  inbox: Receiver<Vec<u8>>,
+  timeout: Cell<Duration>,  // read timeout
+}
+
+
+impl Session {
+  /// Create a session with the peer at the provided address.
+  ///
+  /// Returns the sender used to hand the session datagrams from its peer.
+  pub fn new(socket: UdpSocket, addr: SocketAddr) -> (Sender<Vec<u8>>, Session) {
+    let (tx, rx) = channel();
+    (tx, Session { socket, addr, inbox: rx, timeout: Cell::new(SLEEP_TIME) })
+  }
+}
+
+
+impl Channel for Session {
+  fn send(&self, buf: &[u8]) -> io::Result<usize> {
+    self.socket.send_to(buf, self.addr)
+  }
+
+  fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+    let datagram: Vec<u8> = match self.inbox.recv_timeout(self.timeout.get()) {
+      Ok(d) => d,
+      Err(RecvTimeoutError::Timeout) => return Err(ErrorKind::TimedOut.into()),
+      Err(RecvTimeoutError::Disconnected) => return Err(ErrorKind::NotConnected.into()),
+    };
+
+    // truncate datagrams larger than the buffer, as a socket would
+    let amt: usize = datagram.len().min(buf.len());
+    buf[..amt].copy_from_slice(&datagram[..amt]);
+    Ok(amt)
+  }
+
+  fn set_timeout(&self, timeout: Duration) -> io::Result<()> {
+    self.timeout.set(timeout);
+    Ok(())
+  }
+}
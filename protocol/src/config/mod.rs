@@ -0,0 +1,236 @@
+use std::{collections::HashMap, fs, str::FromStr, time::Duration};
+
+use crate::{log::Level, connection::Limits};
+
+/// Flag naming the config file to read
+const CONFIG_FLAG: &str = "config";
+
+/// Keys configuring transfers, shared by both servers
+pub const LIMIT_KEYS: [&str; 6] = ["retries", "deadline", "timeout", "window", "mtu", "log"];
+
+
+/// Settings of a server, read from command line flags and a config file.
+///
+/// Flags are given as --key value or --key=value, and override the file.
+/// The file holds key = value lines, and keys under a [section] header
+/// only apply to the server of that name, overriding keys before any header:
+///
+/// ```text
+/// # shared by both servers
+/// log = "info"
+///
+/// [datastore]
+/// port = 41001
+/// ```
+pub struct Config {
+  section: String,  // name of the server reading the config
+  flags: HashMap<String, String>,
+  file: HashMap<String, String>,  // keys of a section are prefixed with "<section>."
+  pub positional: Vec<String>,  // arguments that aren't flags
+}
+
+
+impl Config {
+  /// Read the config of the server with the provided name from the
+  /// command line arguments, excluding the program name, and the
+  /// config file they name.
+  ///
+  /// Errors on flags that aren't one of the provided keys.
+  pub fn from_args(section: &str, keys: &[&str], args: &[String]) -> Result<Config, String> {
+    let mut flags: HashMap<String, String> = HashMap::new();
+    let mut positional: Vec<String> = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+      let flag: &str = match arg.strip_prefix("--") {
+        Some(f) => f,
+        None => { positional.push(arg.to_string()); continue; },
+      };
+
+      let (key, value) = match flag.split_once('=') {
+        Some((k, v)) => (k.to_string(), v.to_string()),
+        None => match args.next() {
+          Some(v) => (flag.to_string(), v.to_string()),
+          None => return Err(format!("missing value for --{}", flag)),
+        },
+      };
+
+      if key != CONFIG_FLAG && !keys.contains(&key.as_str()) {
+        return Err(format!("unknown flag --{}", key));
+      }
+      flags.insert(key, value);
+    }
+
+    let file: HashMap<String, String> = match flags.get(CONFIG_FLAG) {
+      Some(path) => match fs::read_to_string(path) {
+        Ok(s) => parse_file(&s)?,
+        Err(e) => return Err(format!("could not read config file {}:\n{}", path, e)),
+      },
+      None => HashMap::new(),
+    };
+
+    Ok(Config { section: section.to_string(), flags, file, positional })
+  }
+
+
+  /// Get the value of a key, if it was set.
+  pub fn get(&self, key: &str) -> Option<&str> {
+    self.flags.get(key)
+      .or_else(|| self.file.get(&format!("{}.{}", self.section, key)))
+      .or_else(|| self.file.get(key))
+      .map(|v| v.as_str())
+  }
+
+
+  /// Parse the value of a key, if it was set.
+  pub fn parse<T: FromStr>(&self, key: &str) -> Result<Option<T>, String> {
+    match self.get(key) {
+      Some(v) => v.parse::<T>().map(Some).map_err(|_| format!("invalid value for {}: {}", key, v)),
+      None => Ok(None),
+    }
+  }
+
+
+  /// Parse the value of a key, or get the provided default if it wasn't set.
+  pub fn parse_or<T: FromStr>(&self, key: &str, default: T) -> Result<T, String> {
+    Ok(self.parse(key)?.unwrap_or(default))
+  }
+
+
+  /// Get the level of messages logged.
+  pub fn log_level(&self) -> Result<Level, String> {
+    self.parse_or("log", Level::INFO)
+  }
+
+
+  /// Get the limits of transfers.
+  ///
+  /// Timeouts are in milliseconds and deadlines in seconds,
+  /// a deadline of 0 leaving transfers unbounded.
+  pub fn limits(&self) -> Result<Limits, String> {
+    let default: Limits = Limits::default();
+    let deadline: u64 = self.parse_or("deadline", 0)?;
+
+    Ok(Limits {
+      retries: self.parse_or("retries", default.retries)?,
+      deadline: if deadline == 0 { None } else { Some(Duration::from_secs(deadline)) },
+      timeout: Duration::from_millis(self.parse_or("timeout", default.timeout.as_millis() as u64)?),
+      window: self.parse_or("window", default.window)?,
+      mtu: self.parse_or("mtu", default.mtu)?,
+    })
+  }
+}
+
+
+/// Parse the keys of a config file, prefixing the keys
+/// of a section with the section's name.
+fn parse_file(s: &str) -> Result<HashMap<String, String>, String> {
+  let mut values: HashMap<String, String> = HashMap::new();
+  let mut section: Option<String> = None;
+
+  for (i, line) in s.lines().enumerate() {
+    let line: &str = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with(';') { continue; }
+
+    if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+      section = Some(name.trim().to_string());
+      continue;
+    }
+
+    let (key, value) = match line.split_once('=') {
+      Some((k, v)) => (k.trim(), parse_value(v.trim())),
+      None => return Err(format!("config line {} is not a key = value pair: {}", i + 1, line)),
+    };
+
+    match &section {
+      Some(name) => values.insert(format!("{}.{}", name, key), value),
+      None => values.insert(key.to_string(), value),
+    };
+  }
+
+  Ok(values)
+}
+
+
+/// Parse a value of a config file, unquoting strings
+/// and removing trailing comments.
+fn parse_value(v: &str) -> String {
+  for quote in ['"', '\''] {
+    if let Some(end) = v.strip_prefix(quote).and_then(|s| s.find(quote)) {
+      return v[1..end + 1].to_string();
+    }
+  }
+
+  match v.find(" #") {
+    Some(i) => v[..i].trim().to_string(),
+    None => v.to_string(),
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  fn config(section: &str, file: &str, args: &[&str]) -> Config {
+    let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    let mut config: Config = Config::from_args(section, &LIMIT_KEYS, &args).unwrap();
+    config.file = parse_file(file).unwrap();
+    config
+  }
+
+
+  #[test]
+  fn quoted_values() {
+    assert_eq!(parse_value("\"info\""), "info");
+    assert_eq!(parse_value("'a # b' # comment"), "a # b");
+    assert_eq!(parse_value("\"a = b\""), "a = b");
+    assert_eq!(parse_value("\"\""), "");
+  }
+
+
+  #[test]
+  fn unquoted_values() {
+    assert_eq!(parse_value("41001 # port"), "41001");
+    assert_eq!(parse_value("a#b"), "a#b");
+    assert_eq!(parse_value("\"unterminated"), "\"unterminated");
+  }
+
+
+  #[test]
+  fn sections_prefix_keys() {
+    let values: HashMap<String, String> = parse_file("# comment\n; comment\nlog = \"debug\"\n\n[ datastore ]\nport = 41001\n[proxy]\nport=8080\n").unwrap();
+    assert_eq!(values.get("log").map(String::as_str), Some("debug"));
+    assert_eq!(values.get("datastore.port").map(String::as_str), Some("41001"));
+    assert_eq!(values.get("proxy.port").map(String::as_str), Some("8080"));
+    assert_eq!(values.get("port"), None);
+  }
+
+
+  #[test]
+  fn line_without_value_is_an_error() {
+    assert!(parse_file("log = info\nport\n").unwrap_err().contains("line 2"));
+  }
+
+
+  #[test]
+  fn section_overrides_file_and_flags_override_both() {
+    let file: &str = "window = 8\nmtu = 1400\n[datastore]\nwindow = 16\n";
+    assert_eq!(config("datastore", file, &[]).get("window"), Some("16"));
+    assert_eq!(config("proxy", file, &[]).get("window"), Some("8"));
+    assert_eq!(config("datastore", file, &["--window", "32"]).get("window"), Some("32"));
+    assert_eq!(config("datastore", file, &["--window=64"]).get("window"), Some("64"));
+    assert_eq!(config("datastore", file, &[]).get("mtu"), Some("1400"));
+  }
+
+
+  #[test]
+  fn invalid_flags() {
+    let args: Vec<String> = vec!["--port".to_string(), "1".to_string()];
+    assert!(Config::from_args("proxy", &LIMIT_KEYS, &args).is_err());
+    let args: Vec<String> = vec!["--window".to_string()];
+    assert!(Config::from_args("proxy", &LIMIT_KEYS, &args).is_err());
+    assert!(config("proxy", "window = many\n", &[]).parse::<usize>("window").is_err());
+  }
+}
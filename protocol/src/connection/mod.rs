@@ -0,0 +1,134 @@
+pub mod rtt;
+
+use std::{io, time::{Duration, Instant}};
+
+use crate::{Channel, Serr, MTU, MIN_MTU, MAX_MTU, RECV_WINDOW, SLEEP_TIME};
+
+use self::rtt::Rtt;
+
+/// Shortest read timeout, as sockets treat a zero timeout as an error
+const MIN_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// Number of consecutive timeouts before the peer is assumed gone
+pub const MAX_RETRIES: usize = 5;
+
+
+/// Bounds on how long a transfer waits on its peer,
+/// and on how much it buffers and accepts.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+  pub retries: usize,  // consecutive timeouts before giving up
+  pub deadline: Option<Duration>,  // longest a transfer may take, unbounded if None
+  pub timeout: Duration,  // timeout before a round trip was measured
+  pub window: usize,  // packets buffered when receiving
+  pub mtu: usize,  // largest datagram accepted
+}
+
+
+impl Default for Limits {
+  fn default() -> Self {
+    Limits { retries: MAX_RETRIES, deadline: None, timeout: SLEEP_TIME, window: RECV_WINDOW, mtu: MTU }
+  }
+}
+
+
+impl Limits {
+  /// Get the longest a transfer may wait on a source of the data it sends,
+  /// being as long as its peer waits on it, the timeout doubling after
+  /// each retry, and no longer than the deadline.
+  pub fn patience(&self) -> Duration {
+    let waits: u32 = (1u32 << (self.retries.min(16) + 1)) - 1;
+    let patience: Duration = self.timeout.saturating_mul(waits);
+
+    match self.deadline {
+      Some(d) => patience.min(d),
+      None => patience,
+    }
+  }
+}
+
+
+/// A transfer over a channel, identified by its connection ID.
+pub struct Connection<'a> {
+  socket: &'a dyn Channel,
+  pub id: u32,
+  pub rtt: Rtt,  // round trip estimate of the transfer
+  pub mtu: usize,  // largest datagram sent or accepted
+  pub limits: Limits,
+  timeouts: usize,  // consecutive timeouts without hearing from the peer
+  deadline: Option<Instant>,  // when the transfer is abandoned
+}
+
+
+impl<'a> Connection<'a> {
+  /// Create a connection with the provided ID over the channel,
+  /// with the default limits.
+  pub fn new(socket: &'a dyn Channel, id: u32) -> Connection<'a> {
+    Connection::with_limits(socket, id, Limits::default())
+  }
+
+
+  /// Create a connection with the provided ID over the channel,
+  /// giving up on the peer as the limits describe.
+  pub fn with_limits(socket: &'a dyn Channel, id: u32, limits: Limits) -> Connection<'a> {
+    let deadline: Option<Instant> = limits.deadline.map(|d| Instant::now() + d);
+    let mtu: usize = limits.mtu.clamp(MIN_MTU, MAX_MTU);
+    Connection { socket, id, rtt: Rtt::with_timeout(limits.timeout), mtu, limits, timeouts: 0, deadline }
+  }
+
+
+  /// Agree on the largest datagram with the peer, given the largest it accepts.
+  pub fn negotiate(&mut self, mtu: usize) {
+    self.mtu = self.mtu.min(mtu).clamp(MIN_MTU, MAX_MTU);
+  }
+
+
+  /// Send a datagram to the peer.
+  pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+    self.socket.send(buf)
+  }
+
+
+  /// Receive a datagram from the peer, waiting at most the
+  /// retransmission timeout.
+  pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+    self.recv_timeout(buf, self.rtt.rto())
+  }
+
+
+  /// Receive a datagram from the peer, waiting at most the provided
+  /// timeout, and no later than the deadline.
+  pub fn recv_timeout(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+    let timeout: Duration = match self.deadline {
+      Some(d) => timeout.min(d.saturating_duration_since(Instant::now())),
+      None => timeout,
+    };
+
+    self.socket.set_timeout(timeout.max(MIN_TIMEOUT))?;
+    self.socket.recv(buf)
+  }
+
+
+  /// Record that the peer didn't answer in time.
+  ///
+  /// Errors once the peer went unanswered for more than
+  /// the allowed retries, or the deadline passed.
+  pub fn timed_out(&mut self) -> Result<(), Serr> {
+    self.timeouts += 1;
+
+    if self.timeouts > self.limits.retries {
+      return Err(Serr::TIMEOUT(format!("Peer did not respond after {} retries", self.limits.retries)));
+    }
+    if self.deadline.is_some_and(|d| Instant::now() >= d) {
+      return Err(Serr::TIMEOUT(format!("Transfer did not complete within {:?}", self.limits.deadline.unwrap_or_default())));
+    }
+
+    Ok(())
+  }
+
+
+  /// Record that the peer answered, resetting its retries.
+  pub fn answered(&mut self) {
+    self.timeouts = 0;
+  }
+}
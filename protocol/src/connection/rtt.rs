@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use crate::SLEEP_TIME;
+
+/// Smallest retransmission timeout
+const MIN_RTO: Duration = Duration::from_millis(20);
+
+/// Largest retransmission timeout, reached by backing off
+const MAX_RTO: Duration = Duration::from_secs(60);
+
+/// Granularity of the clock timing round trips
+const GRANULARITY: Duration = Duration::from_millis(1);
+
+/// Most times the timeout is doubled
+const MAX_BACKOFFS: u32 = 16;
+
+
+/// Estimates a connection's round trip time to derive its
+/// retransmission timeout, as described by RFC 6298.
+pub struct Rtt {
+  srtt: Option<Duration>,  // smoothed round trip time, None until first sample
+  rttvar: Duration,  // round trip time variation
+  rto: Duration,  // retransmission timeout
+  backoffs: u32,  // number of times the timeout doubled since the last sample
+}
+
+
+impl Default for Rtt {
+  fn default() -> Self {
+    Rtt::new()
+  }
+}
+
+
+impl Rtt {
+  /// Create an estimator with no samples, timing out after SLEEP_TIME.
+  pub fn new() -> Rtt {
+    Rtt::with_timeout(SLEEP_TIME)
+  }
+
+
+  /// Create an estimator with no samples, timing out after the provided timeout.
+  pub fn with_timeout(timeout: Duration) -> Rtt {
+    Rtt { srtt: None, rttvar: Duration::ZERO, rto: timeout.clamp(MIN_RTO, MAX_RTO), backoffs: 0 }
+  }
+
+
+  /// Update the estimate with a measured round trip.
+  ///
+  /// Only round trips of packets that weren't retransmitted should
+  /// be sampled, as their ACKs can't be told apart.
+  pub fn sample(&mut self, rtt: Duration) {
+    match self.srtt {
+      None => {
+        self.srtt = Some(rtt);
+        self.rttvar = rtt / 2;
+      },
+      Some(srtt) => {
+        let diff: Duration = srtt.abs_diff(rtt);
+        self.rttvar = (self.rttvar * 3 + diff) / 4;
+        self.srtt = Some((srtt * 7 + rtt) / 8);
+      },
+    }
+
+    let srtt: Duration = self.srtt.unwrap_or(rtt);
+    self.rto = (srtt + GRANULARITY.max(self.rttvar * 4)).clamp(MIN_RTO, MAX_RTO);
+    self.backoffs = 0;
+  }
+
+
+  /// Double the timeout after it expired.
+  pub fn backoff(&mut self) {
+    self.backoffs = (self.backoffs + 1).min(MAX_BACKOFFS);
+  }
+
+
+  /// Undo the backoff once the peer is heard from, for ends
+  /// that can't time round trips to take a new sample.
+  pub fn reset_backoff(&mut self) {
+    self.backoffs = 0;
+  }
+
+
+  /// Get the retransmission timeout.
+  pub fn rto(&self) -> Duration {
+    self.rto.saturating_mul(1 << self.backoffs).min(MAX_RTO)
+  }
+}
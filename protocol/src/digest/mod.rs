@@ -0,0 +1,172 @@
+use std::io::{self, Write};
+
+/// Length of a SHA-256 digest in bytes
+pub const DIGEST_LEN: usize = 32;
+
+/// Length of a SHA-256 block in bytes
+const BLOCK_LEN: usize = 64;
+
+/// Reversed polynomial of the IEEE CRC32
+const CRC_POLY: u32 = 0xEDB8_8320;
+
+/// CRC32 remainders for every byte value
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// SHA-256 initial hash values
+const H0: [u32; 8] = [
+  0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// SHA-256 round constants
+const K: [u32; 64] = [
+  0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+  0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+  0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+  0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+  0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+  0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+  0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+  0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+
+/// Build the lookup table used to compute CRC32s a byte at a time.
+const fn crc_table() -> [u32; 256] {
+  let mut table: [u32; 256] = [0; 256];
+  let mut i: usize = 0;
+
+  while i < 256 {
+    let mut c: u32 = i as u32;
+    let mut bit: usize = 0;
+    while bit < 8 {
+      c = if c & 1 == 1 { CRC_POLY ^ (c >> 1) } else { c >> 1 };
+      bit += 1;
+    }
+    table[i] = c;
+    i += 1;
+  }
+
+  table
+}
+
+
+/// Compute the IEEE CRC32 of the provided bytes.
+pub fn crc32(data: &[u8]) -> u32 {
+  let mut c: u32 = !0;
+
+  for b in data {
+    c = CRC_TABLE[((c ^ *b as u32) & 0xFF) as usize] ^ (c >> 8);
+  }
+
+  !c
+}
+
+
+/// A running SHA-256 digest of a stream of bytes.
+#[derive(Clone)]
+pub struct Sha256 {
+  state: [u32; 8],
+  block: [u8; BLOCK_LEN],  // bytes not yet compressed
+  block_len: usize,  // number of bytes in block
+  len: u64,  // total number of bytes digested
+}
+
+
+impl Default for Sha256 {
+  fn default() -> Self {
+    Sha256::new()
+  }
+}
+
+
+impl Sha256 {
+  /// Create a digest of no bytes.
+  pub fn new() -> Sha256 {
+    Sha256 { state: H0, block: [0; BLOCK_LEN], block_len: 0, len: 0 }
+  }
+
+
+  /// Add the provided bytes to the digest.
+  pub fn update(&mut self, data: &[u8]) {
+    let mut rest: &[u8] = data;
+    let mut amt: usize;
+
+    self.len += data.len() as u64;
+
+    while !rest.is_empty() {
+      amt = (BLOCK_LEN - self.block_len).min(rest.len());
+      self.block[self.block_len..self.block_len + amt].copy_from_slice(&rest[..amt]);
+      self.block_len += amt;
+      rest = &rest[amt..];
+
+      if self.block_len == BLOCK_LEN {
+        compress(&mut self.state, &self.block);
+        self.block_len = 0;
+      }
+    }
+  }
+
+
+  /// Pad the digested bytes and produce the final digest.
+  pub fn finish(mut self) -> [u8; DIGEST_LEN] {
+    let bit_len: u64 = self.len * 8;
+    let mut digest: [u8; DIGEST_LEN] = [0; DIGEST_LEN];
+
+    // append a 1 bit, then 0 bits until there's room for the length
+    self.update(&[0x80]);
+    while self.block_len != BLOCK_LEN - 8 {
+      self.update(&[0]);
+    }
+    self.update(&bit_len.to_be_bytes());
+
+    for (i, word) in self.state.iter().enumerate() {
+      digest[i * 4..(i + 1) * 4].copy_from_slice(&word.to_be_bytes());
+    }
+
+    digest
+  }
+}
+
+
+/// Bytes written to a digest are added to it, so readers can be digested with io::copy.
+impl Write for Sha256 {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.update(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+
+/// Mix a full block into the hash state.
+fn compress(state: &mut [u32; 8], block: &[u8; BLOCK_LEN]) {
+  let mut w: [u32; 64] = [0; 64];
+  let mut v: [u32; 8] = *state;
+
+  for i in 0..16 {
+    w[i] = u32::from_be_bytes([block[i * 4], block[i * 4 + 1], block[i * 4 + 2], block[i * 4 + 3]]);
+  }
+  for i in 16..64 {
+    let s0: u32 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+    let s1: u32 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+    w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+  }
+
+  for i in 0..64 {
+    let s1: u32 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+    let ch: u32 = (v[4] & v[5]) ^ (!v[4] & v[6]);
+    let t1: u32 = v[7].wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+    let s0: u32 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+    let maj: u32 = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+    let t2: u32 = s0.wrapping_add(maj);
+
+    v = [t1.wrapping_add(t2), v[0], v[1], v[2], v[3].wrapping_add(t1), v[4], v[5], v[6]];
+  }
+
+  for (s, x) in state.iter_mut().zip(v.iter()) {
+    *s = s.wrapping_add(*x);
+  }
+}
@@ -0,0 +1,857 @@
+#![allow(clippy::upper_case_acronyms, clippy::needless_late_init)]
+
+pub mod send;
+pub mod receive;
+pub mod digest;
+pub mod connection;
+pub mod config;
+pub mod log;
+pub mod storage;
+pub mod listing;
+
+use core::time;
+use digest::{DIGEST_LEN, crc32};
+use connection::Connection;
+use std::{time::{Duration, Instant, SystemTime}, net::UdpSocket, io, collections::hash_map::RandomState, hash::{BuildHasher, Hasher}, sync::atomic::{AtomicU64, Ordering}};
+
+/// Minimum Ethernet MTU in bytes
+const ETHER_MTU: usize = 1500;
+
+/// Minimum IPv6 MTU in bytes
+const IPV6_MTU: usize = 1280;
+
+/// Jumbo frame MTU in bytes
+const JUMBO_MTU: usize = 9000;
+
+/// UDP header length in bytes
+const UDP_H_LEN: usize = 8;
+
+/// IP header length in bytes
+const IP_H_LEN: usize = 20;
+
+/// IPv6 header length in bytes
+const IPV6_H_LEN: usize = 40;
+
+/// Largest datagram over Ethernet in bytes, the default
+/// largest datagram a connection accepts
+pub const MTU: usize = ETHER_MTU - UDP_H_LEN - IP_H_LEN;
+
+/// Largest datagram every IPv6 path carries in bytes,
+/// DATA is sent at this size until larger sizes are probed
+pub const MIN_MTU: usize = IPV6_MTU - UDP_H_LEN - IPV6_H_LEN;
+
+/// Largest datagram over jumbo frames in bytes, and the size of receive buffers
+pub const MAX_MTU: usize = JUMBO_MTU - UDP_H_LEN - IP_H_LEN;
+
+/// Port the datastore listens on
+pub const DATASTORE_PORT: u16 = 41000;
+
+/// ASCII value for line feed
+pub const LF: u8 = 10;
+
+/// ASCII value for carriage return
+pub const CR: u8 = 13;
+
+/// Byte sequence of <CR><LF>
+pub const CRLF: [u8; 2] = [CR, LF];
+
+/// Length of flags field in bytes
+pub const FLAGS_LEN: usize = 1;
+
+/// Length of connection ID field in bytes
+pub const CONN_LEN: usize = 4;
+
+/// Length of sequence number field in bytes
+pub const SEQ_LEN: usize = 8;
+
+/// Length of checksum field in bytes
+pub const CHECKSUM_LEN: usize = 4;
+
+/// Starting byte position of sequence number field
+const SEQ_START: usize = FLAGS_LEN + CONN_LEN;
+
+/// Starting byte position of checksum field
+const CHECKSUM_START: usize = SEQ_START + SEQ_LEN;
+
+/// Length of the header
+pub const HEADER_LEN: usize = FLAGS_LEN + CONN_LEN + SEQ_LEN + CHECKSUM_LEN;
+
+/// Largest body of a control packet in bytes, so control packets
+/// are never larger than the datagrams every path carries
+pub const BODY_LEN: usize = MIN_MTU - HEADER_LEN;
+
+/// Starting byte position of body field
+pub const BODY_START: usize = HEADER_LEN;
+
+/// Number of packets sent before the first ACK, and before
+/// the slow start after a timeout
+const INITIAL_WINDOW: usize = 4;
+
+/// Number of packets buffered by the receiver, advertised in its ACKs
+const RECV_WINDOW: usize = 512;
+
+/// Number of DATA packets received between ACKs, acking every
+/// packet so the sender counts duplicate ACKs as soon as one is lost
+const ACK_EVERY: usize = 1;
+
+/// Length of the advertised window stored in the body of an ACK
+const WINDOW_LEN: usize = 4;
+
+/// Length of the largest datagram accepted, stored after the window in the body of an ACK
+const MTU_LEN: usize = 4;
+
+/// Most ranges of bytes received past the acked byte stored in
+/// the body of an ACK, after the number of ranges
+const MAX_SACKS: usize = 32;
+
+/// Length of the modification time stored in the body of a STAT's ACK
+const MTIME_LEN: usize = 8;
+
+/// Length of the offset and length of a span stored in the body of a GET's SYN ACK
+const SPAN_LEN: usize = SEQ_LEN * 2;
+
+/// Offset of a span holding the end of a file, whose offset depends on the file's size
+const SUFFIX: u64 = u64::MAX;
+
+/// Length of a range of bytes in the body of an ACK
+const SACK_LEN: usize = SEQ_LEN * 2;
+
+/// Starting byte position of the ranges in the body of an ACK
+const SACK_START: usize = BODY_START + WINDOW_LEN + MTU_LEN + 1;
+
+/// Flags field value for SYN
+const SYN: u8 = 32;
+
+/// Flags field value for GET
+pub const GET: u8 = SYN | 8;
+
+/// Flags field value for POST
+pub const POST: u8 = SYN | 16;
+
+/// Flags field value for DELETE
+pub const DELETE: u8 = SYN | 4;
+
+/// Flags field value for STAT
+pub const STAT: u8 = SYN | 2;
+
+/// Flags field value for LIST
+pub const LIST: u8 = SYN | 12;
+
+/// Flags field value for starting a resumable upload
+pub const CREATE: u8 = SYN | 6;
+
+/// Flags field value for querying how much of a resumable upload was received
+pub const OFFSET: u8 = SYN | 10;
+
+/// Flags field value for continuing a resumable upload
+pub const PATCH: u8 = SYN | 14;
+
+/// Flags field value for abandoning a resumable upload
+pub const ABORT: u8 = SYN | 18;
+
+/// Flags field value for ACK
+pub const ACK: u8 = 128;
+
+/// Flags field value for SYN ACK
+pub const SYNACK: u8 = ACK | SYN;
+
+/// Flag indicating done with connection
+const DONE: u8 = 1;
+
+/// Flag for file not existing
+pub const FLAG_404: u8 = 4 | DONE;
+
+/// Flag for a path outside of the storage root
+pub const FLAG_403: u8 = 8 | DONE;
+
+/// Flag for a malformed path
+pub const FLAG_400: u8 = 16 | DONE;
+
+/// Flag for a range of bytes outside of the file
+pub const FLAG_416: u8 = 32 | DONE;
+
+/// Flag for continuing a resumable upload from the wrong offset,
+/// or while it's being continued
+pub const FLAG_409: u8 = 6 | DONE;
+
+/// Flag for server error
+pub const FLAG_500: u8 = 2 | DONE;
+
+/// Flag for terminating connection
+pub const FIN: u8 = ACK | DONE;
+
+/// Flags field value for data
+pub const DATA: u8 = 64;
+
+/// Amount of time to wait in milliseconds
+const WAIT_TIME: u64 = 250;
+
+/// Duration of time to sleep
+pub const SLEEP_TIME: Duration = time::Duration::from_millis(WAIT_TIME);
+
+/// Number of timeouts in a row after which a peer is assumed
+/// to have received the reply to its request
+const LINGER_TIMEOUTS: usize = 3;
+
+/// Number of random numbers generated, keeping those generated at once apart
+static RANDOMS: AtomicU64 = AtomicU64::new(0);
+
+
+/// A datagram channel to a single peer.
+pub trait Channel {
+  /// Send a datagram to the peer.
+  fn send(&self, buf: &[u8]) -> io::Result<usize>;
+
+  /// Receive a datagram from the peer, failing if none arrives
+  /// before the channel's read timeout.
+  fn recv(&self, buf: &mut [u8]) -> io::Result<usize>;
+
+  /// Set the channel's read timeout.
+  fn set_timeout(&self, timeout: Duration) -> io::Result<()>;
+}
+
+
+/// A UDP socket connected to its peer is a channel to that peer.
+impl Channel for UdpSocket {
+  fn send(&self, buf: &[u8]) -> io::Result<usize> {
+    UdpSocket::send(self, buf)
+  }
+
+  fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+    UdpSocket::recv(self, buf)
+  }
+
+  fn set_timeout(&self, timeout: Duration) -> io::Result<()> {
+    self.set_read_timeout(Some(timeout))
+  }
+}
+
+
+/// Enum of the possible errors.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum Serr {
+  DNE(String),
+  FORBIDDEN(String),
+  BAD(String),
+  SERVER(String),
+  TIMEOUT(String),
+  RANGE(String),
+  CONFLICT(String),
+  NA,
+}
+
+
+/// Enum of the states of a receiving buffer.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum ReadData {
+  MORE,
+  DONE,
+}
+
+
+/// Build a packet body holding the provided filename.
+///
+/// The body format is:
+/// <PATH><CR><LF>
+///
+/// Filenames holding a CR would be cut short by the peer, and are malformed.
+pub fn filename_as_body(filename: &str) -> Result<Vec<u8>, Serr> {
+  let file_bytes: &[u8] = filename.as_bytes();
+
+  if file_bytes.contains(&CR) {
+    return Err(Serr::BAD(format!("filename {:?} may not contain a carriage return", filename)));
+  }
+
+  if file_bytes.len() > (BODY_LEN - 2) {  // account for trailing <CR><LF>
+    return Err(Serr::SERVER(format!("filename exceeds {} bytes, cannot fit into packet", (BODY_LEN - 2))));
+  }
+
+  Ok([file_bytes, &CRLF].concat())
+}
+
+
+/// Build a FIN body holding the provided filename and the digest
+/// of the file's contents.
+///
+/// The body format is:
+/// <PATH><CR><LF><DIGEST>
+pub fn fin_body(filename: &str, digest: &[u8; DIGEST_LEN]) -> Result<Vec<u8>, Serr> {
+  let mut data: Vec<u8> = filename_as_body(filename)?;
+
+  if data.len() + DIGEST_LEN > BODY_LEN {
+    return Err(Serr::SERVER(format!("filename exceeds {} bytes, cannot fit into FIN", (BODY_LEN - CRLF.len() - DIGEST_LEN))));
+  }
+
+  data.extend_from_slice(digest);
+  Ok(data)
+}
+
+
+/// Build the body of the ACK answering a STAT, holding the file's
+/// modification time in seconds since the Unix epoch and the digest
+/// of its contents. The file's size is the ACK's sequence number.
+///
+/// The body format is:
+/// <MTIME><DIGEST>
+pub fn stat_body(mtime: u64, digest: &[u8; DIGEST_LEN]) -> Vec<u8> {
+  [&mtime.to_be_bytes()[..], digest].concat()
+}
+
+
+/// Parse out the modification time and digest stored in the body of a STAT's ACK.
+pub fn get_stat(buf: &[u8]) -> Result<(u64, [u8; DIGEST_LEN]), Serr> {
+  let mtime = buf.get(BODY_START..BODY_START + MTIME_LEN)
+  .and_then(|b| b.try_into().ok());
+  let digest = buf.get(BODY_START + MTIME_LEN..BODY_START + MTIME_LEN + DIGEST_LEN)
+  .and_then(|b| b.try_into().ok());
+
+  match (mtime, digest) {
+    (Some(m), Some(d)) => Ok((u64::from_be_bytes(m), d)),
+    _ => Err(Serr::SERVER(format!("out of bounds: there were not {} bytes between starting index {} and end of buffer of size {}", MTIME_LEN + DIGEST_LEN, BODY_START, buf.len()))),
+  }
+}
+
+
+/// A span of bytes of a file requested by a GET.
+#[derive(Debug, Clone, Copy)]
+#[derive(PartialEq)]
+pub struct Span {
+  pub offset: Option<u64>,  // first byte, None for the last length bytes of the file
+  pub length: Option<u64>,  // number of bytes, None for up to the end of the file
+}
+
+
+impl Span {
+  /// Get the offset and length of the span within a file of the provided size,
+  /// the span being cut short at the end of the file.
+  ///
+  /// Returns None if the span holds no byte of the file, unless it's
+  /// the whole file, which may be empty.
+  pub fn within(&self, size: u64) -> Option<(u64, u64)> {
+    let (offset, length): (u64, u64) = match (self.offset, self.length) {
+      (Some(0), None) | (None, None) => return Some((0, size)),
+      (Some(o), l) => (o, l.unwrap_or(u64::MAX)),
+      (None, Some(l)) => (size.saturating_sub(l), l.min(size)),
+    };
+
+    match offset < size && length > 0 {
+      true => Some((offset, length.min(size - offset))),
+      false => None,
+    }
+  }
+}
+
+
+/// Build the body of a GET for a span of the file with the provided filename.
+///
+/// The body format is:
+/// <PATH><CR><LF><OFFSET><LENGTH>
+///
+/// An offset of u64::MAX requests the last length bytes of the file,
+/// and a length of u64::MAX requests up to the end of the file.
+pub fn span_body(filename: &str, span: &Span) -> Result<Vec<u8>, Serr> {
+  fields_body(filename, &[span.offset.unwrap_or(SUFFIX), span.length.unwrap_or(u64::MAX)], "GET")
+}
+
+
+/// Parse out the span stored after the filename in the body of a GET,
+/// which is the whole file if the GET holds none.
+pub fn get_span(buf: &[u8]) -> Result<Span, Serr> {
+  let [offset, length] = match get_fields(buf)? {
+    Some(f) => f,
+    None => return Ok(Span { offset: Some(0), length: None }),
+  };
+
+  Ok(Span {
+    offset: if offset == SUFFIX { None } else { Some(offset) },
+    length: if length == u64::MAX { None } else { Some(length) },
+  })
+}
+
+
+/// Build the body of a PATCH continuing the upload with the provided ID
+/// of the file with the provided filename, from the provided offset.
+/// The length of the data sent is the PATCH's sequence number.
+///
+/// The body format is:
+/// <PATH><CR><LF><ID><OFFSET>
+pub fn patch_body(filename: &str, id: u64, offset: u64) -> Result<Vec<u8>, Serr> {
+  fields_body(filename, &[id, offset], "PATCH")
+}
+
+
+/// Parse out the upload ID and offset stored after the filename in the body of a PATCH.
+pub fn get_patch(buf: &[u8]) -> Result<(u64, u64), Serr> {
+  match get_fields(buf)? {
+    Some([id, offset]) => Ok((id, offset)),
+    None => Err(Serr::SERVER("PATCH does not have room for an upload ID and offset".to_string())),
+  }
+}
+
+
+/// Build a body holding the provided filename followed by the provided fields.
+fn fields_body(filename: &str, fields: &[u64], packet: &str) -> Result<Vec<u8>, Serr> {
+  let mut data: Vec<u8> = filename_as_body(filename)?;
+
+  if data.len() + fields.len() * SEQ_LEN > BODY_LEN {
+    return Err(Serr::SERVER(format!("filename exceeds {} bytes, cannot fit into {}", (BODY_LEN - CRLF.len() - fields.len() * SEQ_LEN), packet)));
+  }
+
+  for f in fields {
+    data.extend_from_slice(&f.to_be_bytes());
+  }
+  Ok(data)
+}
+
+
+/// Parse out the fields stored after the filename in the body of a packet,
+/// if it holds them.
+fn get_fields<const N: usize>(buf: &[u8]) -> Result<Option<[u64; N]>, Serr> {
+  let start: usize = filename_end(buf)? + CRLF.len();
+  let mut fields: [u64; N] = [0; N];
+
+  for (i, f) in fields.iter_mut().enumerate() {
+    match buf.get(start + i * SEQ_LEN..start + (i + 1) * SEQ_LEN).and_then(|b| b.try_into().ok()) {
+      Some(b) => *f = u64::from_be_bytes(b),
+      None => return Ok(None),
+    }
+  }
+  Ok(Some(fields))
+}
+
+
+/// Build the body of the SYN ACK answering a GET, holding the offset of
+/// the span sent and the size of the whole file. The length of the span
+/// is the SYN ACK's sequence number.
+///
+/// The body format is:
+/// <OFFSET><SIZE>
+pub fn synack_body(offset: u64, size: u64) -> Vec<u8> {
+  [offset.to_be_bytes(), size.to_be_bytes()].concat()
+}
+
+
+/// Parse out the offset of the span sent and the size of the whole file
+/// stored in the body of a GET's SYN ACK.
+pub fn get_synack(buf: &[u8]) -> Result<(u64, u64), Serr> {
+  let offset = buf.get(BODY_START..BODY_START + SEQ_LEN)
+  .and_then(|b| b.try_into().ok());
+  let size = buf.get(BODY_START + SEQ_LEN..BODY_START + SPAN_LEN)
+  .and_then(|b| b.try_into().ok());
+
+  match (offset, size) {
+    (Some(o), Some(s)) => Ok((u64::from_be_bytes(o), u64::from_be_bytes(s))),
+    _ => Err(Serr::SERVER(format!("out of bounds: there were not {} bytes between starting index {} and end of buffer of size {}", SPAN_LEN, BODY_START, buf.len()))),
+  }
+}
+
+
+/// Parse out the size of the whole file stored in the body of the ACK answering
+/// an OFFSET. The number of bytes received so far is the ACK's sequence number.
+///
+/// The body format is:
+/// <LENGTH>
+pub fn get_length(buf: &[u8]) -> Result<u64, Serr> {
+  match buf.get(BODY_START..BODY_START + SEQ_LEN).and_then(|b| b.try_into().ok()) {
+    Some(l) => Ok(u64::from_be_bytes(l)),
+    None => Err(Serr::SERVER(format!("out of bounds: there were not {} bytes between starting index {} and end of buffer of size {}", SEQ_LEN, BODY_START, buf.len()))),
+  }
+}
+
+
+/// Find the end of the filename stored in the body of a packet.
+fn filename_end(buf: &[u8]) -> Result<usize, Serr> {
+  match buf.iter().skip(BODY_START).position(|&x| x == CR) {
+    Some(i) => Ok(BODY_START + i),
+    None => Err(Serr::SERVER("Cannot determine filename".to_string())),
+  }
+}
+
+
+/// Parse out the filename stored in the body of a packet.
+///
+/// Filenames that aren't valid UTF-8 are malformed.
+pub fn get_filename(buf: &[u8]) -> Result<String, Serr> {
+  match String::from_utf8(buf[BODY_START..filename_end(buf)?].to_vec()) {
+    Ok(f) => Ok(f),
+    Err(_) => Err(Serr::BAD("Filename is not valid UTF-8".to_string())),
+  }
+}
+
+
+/// Parse out the digest stored after the filename in the body of a FIN.
+pub fn get_digest(buf: &[u8]) -> Result<[u8; DIGEST_LEN], Serr> {
+  let start: usize = filename_end(buf)? + CRLF.len();
+
+  let mut digest: [u8; DIGEST_LEN] = [0; DIGEST_LEN];
+
+  match buf.get(start..start + DIGEST_LEN) {
+    Some(d) => { digest.copy_from_slice(d); Ok(digest) },
+    None => Err(Serr::SERVER("FIN does not have room for a digest".to_string())),
+  }
+}
+
+
+/// Get connection ID as a u32.
+pub fn get_conn_id(buf: &[u8]) -> Result<u32, Serr> {
+  let bytes = buf.get(FLAGS_LEN..SEQ_START)
+  .and_then(|b| b.try_into().ok());
+
+  match bytes {
+    Some(i) => Ok(u32::from_be_bytes(i)),
+    None => Err(Serr::SERVER(format!("out of bounds: there were not 4 bytes between starting index {} and end of buffer of size {}", FLAGS_LEN, buf.len())))
+  }
+}
+
+
+/// Get the window advertised in an ACK as a number of packets.
+pub fn get_window(buf: &[u8]) -> Result<usize, Serr> {
+  let bytes = buf.get(BODY_START..BODY_START + WINDOW_LEN)
+  .and_then(|b| b.try_into().ok());
+
+  match bytes {
+    Some(i) => Ok(u32::from_be_bytes(i) as usize),
+    None => Err(Serr::SERVER(format!("out of bounds: there were not 4 bytes between starting index {} and end of buffer of size {}", BODY_START, buf.len())))
+  }
+}
+
+
+/// Get the largest datagram the sender of an ACK accepts.
+pub fn get_mtu(buf: &[u8]) -> Result<usize, Serr> {
+  let start: usize = BODY_START + WINDOW_LEN;
+  let bytes = buf.get(start..start + MTU_LEN)
+  .and_then(|b| b.try_into().ok());
+
+  match bytes {
+    Some(i) => Ok(u32::from_be_bytes(i) as usize),
+    None => Err(Serr::SERVER(format!("out of bounds: there were not 4 bytes between starting index {} and end of buffer of size {}", start, buf.len())))
+  }
+}
+
+
+/// Get the ranges of bytes an ACK selectively acknowledges.
+///
+/// Ranges are past the acked byte, inclusive start, exclusive end.
+pub fn get_sack(buf: &[u8]) -> Result<Vec<(u64, u64)>, Serr> {
+  let count: usize = match buf.get(SACK_START - 1) {
+    Some(c) => (*c as usize).min(MAX_SACKS),
+    None => return Err(Serr::SERVER(format!("out of bounds: there was no range count at index {} of buffer of size {}", SACK_START - 1, buf.len()))),
+  };
+
+  let mut ranges: Vec<(u64, u64)> = Vec::with_capacity(count);
+  for i in 0..count {
+    let start: usize = SACK_START + i * SACK_LEN;
+    let bytes = buf.get(start..start + SACK_LEN)
+    .and_then(|b| Some((b[..SEQ_LEN].try_into().ok()?, b[SEQ_LEN..].try_into().ok()?)));
+
+    match bytes {
+      Some((s, e)) => ranges.push((u64::from_be_bytes(s), u64::from_be_bytes(e))),
+      None => return Err(Serr::SERVER(format!("out of bounds: there were not {} bytes between starting index {} and end of buffer of size {}", SACK_LEN, start, buf.len()))),
+    }
+  }
+
+  Ok(ranges)
+}
+
+
+/// Get sequence number as a u64.
+pub fn get_seq(buf: &[u8]) -> Result<u64, Serr> {
+  let bytes = buf.get(SEQ_START..SEQ_START + SEQ_LEN)
+  .and_then(|b| b.try_into().ok());
+
+  match bytes {
+    Some(i) => Ok(u64::from_be_bytes(i)),
+    None => Err(Serr::SERVER(format!("out of bounds: there were not 8 bytes between starting index {} and end of buffer of size {}", SEQ_START, buf.len())))
+  }
+}
+
+
+/// Get checksum of the packet's body as a u32.
+pub fn get_checksum(buf: &[u8]) -> Result<u32, Serr> {
+  let bytes = buf.get(CHECKSUM_START..CHECKSUM_START + CHECKSUM_LEN)
+  .and_then(|b| b.try_into().ok());
+
+  match bytes {
+    Some(i) => Ok(u32::from_be_bytes(i)),
+    None => Err(Serr::SERVER(format!("out of bounds: there were not 4 bytes between starting index {} and end of buffer of size {}", CHECKSUM_START, buf.len())))
+  }
+}
+
+
+/// Determine if the packet's body matches its checksum.
+pub fn valid_checksum(buf: &[u8]) -> Result<bool, Serr> {
+  Ok(get_checksum(buf)? == crc32(&buf[BODY_START..]))
+}
+
+
+/// Generate an ID for a new connection.
+///
+/// IDs are random and never 0, so packets from an earlier
+/// transfer are unlikely to match a new one.
+pub fn new_conn_id() -> u32 {
+  match random() as u32 {
+    0 => 1,
+    id => id,
+  }
+}
+
+
+/// Generate a random number, e.g. an ID that can't be guessed.
+///
+/// Numbers generated at the same time still differ.
+pub fn random() -> u64 {
+  let mut hasher = RandomState::new().build_hasher();
+  if let Ok(t) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+    hasher.write_u128(t.as_nanos());
+  }
+  hasher.write_u64(RANDOMS.fetch_add(1, Ordering::Relaxed));
+  hasher.finish()
+}
+
+
+/// Create a packet with the provided header info and no body.
+pub fn create_header(flag: u8, id: u32, seq: u64) -> Vec<u8> {
+  let mut pkt: Vec<u8> = vec![0; HEADER_LEN];
+
+  pkt[0] = flag;
+  pkt[FLAGS_LEN..SEQ_START].copy_from_slice(&id.to_be_bytes());
+  pkt[SEQ_START..SEQ_START + SEQ_LEN].copy_from_slice(&seq.to_be_bytes());
+  pkt[CHECKSUM_START..CHECKSUM_START + CHECKSUM_LEN].copy_from_slice(&crc32(&[]).to_be_bytes());
+
+  pkt
+}
+
+
+/// Build a packet from the provided flag, connection ID,
+/// sequence number, and data, checksumming the data.
+///
+/// The packet is as long as the data.
+pub fn create_pkt(flag: u8, id: u32, seq: u64, data: &[u8]) -> Vec<u8> {
+  let mut pkt: Vec<u8> = create_header(flag, id, seq);
+
+  pkt[CHECKSUM_START..CHECKSUM_START + CHECKSUM_LEN].copy_from_slice(&crc32(data).to_be_bytes());
+  pkt.extend_from_slice(data);
+
+  pkt
+}
+
+
+/// Create an ACK for the provided sequence number, advertising
+/// the number of packets the receiver can buffer and the largest
+/// datagram it accepts, and selectively acknowledging the ranges
+/// of bytes received past the sequence number.
+///
+/// The body format is:
+/// <WINDOW><MTU><RANGE COUNT>[<RANGE START><RANGE END>...]
+///
+/// The body is only as long as the ranges it holds.
+fn create_ack(id: u32, seq: u64, window: usize, mtu: usize, sack: &[(u64, u64)]) -> Vec<u8> {
+  let count: usize = sack.len().min(MAX_SACKS);
+  let mut body: Vec<u8> = Vec::with_capacity(SACK_START - BODY_START + count * SACK_LEN);
+  let window: u32 = window.try_into().unwrap_or(u32::MAX);
+  let mtu: u32 = mtu.try_into().unwrap_or(u32::MAX);
+
+  body.extend_from_slice(&window.to_be_bytes());
+  body.extend_from_slice(&mtu.to_be_bytes());
+  body.push(count as u8);
+  for (s, e) in sack.iter().take(count) {
+    body.extend_from_slice(&s.to_be_bytes());
+    body.extend_from_slice(&e.to_be_bytes());
+  }
+  create_pkt(ACK, id, seq, &body)
+}
+
+
+/// Send a buffer over the connection, ensuring its delivery.
+///
+/// Returns the first reply of the connection carrying the provided
+/// flags, or an error if the peer replies with an error flag.
+pub fn send_buf(conn: &mut Connection, buf: &[u8], flags: u8, filename: &str) -> Result<Vec<u8>, Serr> {
+  let mut amt;
+  let mut received: [u8; MAX_MTU];
+  let mut sent: Instant;
+  let mut retransmitted: bool = false;
+
+  loop {
+    received = [0; MAX_MTU];
+    let _ = conn.send(buf);
+    sent = Instant::now();
+
+    // read ack
+    amt = match conn.recv(&mut received) {
+      Ok(i) => i,
+      Err(_) => {
+        conn.timed_out()?;
+        conn.rtt.backoff();
+        retransmitted = true;
+        continue
+      },
+    };
+
+    if amt >= HEADER_LEN {  // don't require ack to have body
+      // drop stale replies from an earlier transfer
+      if get_conn_id(&received)? != conn.id { retransmitted = true; continue; }
+
+      // if flags match
+      if received[0] == flags {
+        if !retransmitted { conn.rtt.sample(sent.elapsed()); }
+        conn.answered();
+        return Ok(received[..amt].to_vec())
+      }
+
+      if received[0] == FLAG_404 {
+        return Err(Serr::DNE(format!("{} does not exist", filename)));
+      }
+
+      if received[0] == FLAG_403 {
+        return Err(Serr::FORBIDDEN(format!("{} is outside of the storage root", filename)));
+      }
+
+      if received[0] == FLAG_400 {
+        return Err(Serr::BAD(format!("{} is not a valid path", filename)));
+      }
+
+      if received[0] == FLAG_416 {
+        return Err(Serr::RANGE(format!("range requested is outside of {}", filename)));
+      }
+
+      if received[0] == FLAG_409 {
+        return Err(Serr::CONFLICT(format!("upload of {} is not where it was expected", filename)));
+      }
+
+      if received[0] == FLAG_500 {
+        return Err(Serr::SERVER(format!("error with {}", filename)));
+      }
+    }
+    retransmitted = true;
+  }
+}
+
+
+/// Answer retransmissions of the request that started the connection
+/// with the provided reply, until the peer stops sending them.
+///
+/// Requests answered by a single reply don't keep a connection open,
+/// so a lost reply would otherwise have its request handled twice.
+pub fn linger(conn: &mut Connection, reply: &[u8]) {
+  let mut received: [u8; MAX_MTU];
+  let mut quiet: usize = 0;
+
+  let _ = conn.send(reply);
+  while quiet < LINGER_TIMEOUTS {
+    received = [0; MAX_MTU];
+    match conn.recv(&mut received) {
+      Ok(amt) if amt >= HEADER_LEN && get_conn_id(&received).is_ok_and(|id| id == conn.id) => {
+        let _ = conn.send(reply);
+      },
+      Ok(_) => (),
+      Err(_) => {
+        conn.rtt.backoff();
+        quiet += 1;
+      },
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  #[test]
+  fn whole_file_span_of_empty_file() {
+    assert_eq!(Span { offset: Some(0), length: None }.within(0), Some((0, 0)));
+    assert_eq!(Span { offset: None, length: None }.within(0), Some((0, 0)));
+    assert_eq!(Span { offset: Some(0), length: None }.within(10), Some((0, 10)));
+  }
+
+
+  #[test]
+  fn span_cut_short_at_end_of_file() {
+    assert_eq!(Span { offset: Some(4), length: Some(100) }.within(10), Some((4, 6)));
+    assert_eq!(Span { offset: Some(4), length: None }.within(10), Some((4, 6)));
+    assert_eq!(Span { offset: Some(0), length: Some(1) }.within(10), Some((0, 1)));
+  }
+
+
+  #[test]
+  fn span_past_end_of_file() {
+    assert_eq!(Span { offset: Some(10), length: None }.within(10), None);
+    assert_eq!(Span { offset: Some(0), length: Some(1) }.within(0), None);
+    assert_eq!(Span { offset: Some(1), length: None }.within(0), None);
+  }
+
+
+  #[test]
+  fn suffix_span() {
+    assert_eq!(Span { offset: None, length: Some(3) }.within(10), Some((7, 3)));
+    assert_eq!(Span { offset: None, length: Some(30) }.within(10), Some((0, 10)));
+    assert_eq!(Span { offset: None, length: Some(0) }.within(10), None);
+    assert_eq!(Span { offset: None, length: Some(3) }.within(0), None);
+  }
+
+
+  #[test]
+  fn filename_is_utf8() {
+    let pkt: Vec<u8> = create_pkt(POST, 1, 0, &filename_as_body("./café.txt").unwrap());
+    assert_eq!(get_filename(&pkt).unwrap(), "./café.txt");
+
+    let pkt: Vec<u8> = create_pkt(POST, 1, 0, &[b"./caf\xe9.txt".as_slice(), &CRLF].concat());
+    assert!(matches!(get_filename(&pkt), Err(Serr::BAD(_))));
+  }
+
+
+  #[test]
+  fn filename_without_cr() {
+    assert!(matches!(filename_as_body("./a\rb.txt"), Err(Serr::BAD(_))));
+  }
+
+
+  #[test]
+  fn span_round_trips_through_get() {
+    for span in [Span { offset: Some(5), length: Some(7) }, Span { offset: None, length: Some(7) }, Span { offset: Some(5), length: None }] {
+      let pkt: Vec<u8> = create_pkt(GET, 1, 0, &span_body("./a.txt", &span).unwrap());
+      assert_eq!(get_span(&pkt).unwrap(), span);
+      assert_eq!(get_filename(&pkt).unwrap(), "./a.txt");
+    }
+  }
+
+
+  #[test]
+  fn ack_round_trips() {
+    let pkt: Vec<u8> = create_ack(7, 100, 64, 1400, &[(200, 300), (400, 450)]);
+    assert!(valid_checksum(&pkt).unwrap());
+    assert_eq!(get_seq(&pkt).unwrap(), 100);
+    assert_eq!(get_window(&pkt).unwrap(), 64);
+    assert_eq!(get_mtu(&pkt).unwrap(), 1400);
+    assert_eq!(get_sack(&pkt).unwrap(), vec![(200, 300), (400, 450)]);
+  }
+
+
+  #[test]
+  fn ack_without_sack() {
+    let pkt: Vec<u8> = create_ack(7, 100, 0, MIN_MTU, &[]);
+    assert_eq!(pkt.len(), SACK_START);
+    assert_eq!(get_window(&pkt).unwrap(), 0);
+    assert_eq!(get_sack(&pkt).unwrap(), vec![]);
+  }
+
+
+  #[test]
+  fn sack_holds_at_most_max_ranges() {
+    let sack: Vec<(u64, u64)> = (0..MAX_SACKS as u64 + 5).map(|i| (i * 10, i * 10 + 5)).collect();
+    let pkt: Vec<u8> = create_ack(7, 0, 1, MIN_MTU, &sack);
+    assert_eq!(get_sack(&pkt).unwrap(), sack[..MAX_SACKS].to_vec());
+  }
+
+
+  #[test]
+  fn sack_cut_short_is_an_error() {
+    let pkt: Vec<u8> = create_ack(7, 0, 1, MIN_MTU, &[(10, 20)]);
+    assert!(get_sack(&pkt[..pkt.len() - 1]).is_err());
+    assert!(get_window(&pkt[..BODY_START + 2]).is_err());
+  }
+}
@@ -0,0 +1,96 @@
+use std::{fs, io, path::Path, time::UNIX_EPOCH};
+
+use crate::{Serr, storage::reserved};
+
+/// Length of the fixed fields of an encoded entry: size, mtime,
+/// directory flag and name length
+const FIELDS_LEN: usize = 8 + 8 + 1 + 2;
+
+
+/// An entry of a directory listing.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Entry {
+  pub name: String,
+  pub size: u64,  // bytes, 0 for directories
+  pub mtime: u64,  // seconds since the Unix epoch
+  pub dir: bool,
+}
+
+
+/// List the entries of the directory at the provided path, sorted by name.
+///
+/// Files the server keeps next to stored files, such as uploads
+/// in progress and kept digests, are left out.
+pub fn list(dir: &Path) -> io::Result<Vec<Entry>> {
+  let mut entries: Vec<Entry> = Vec::new();
+
+  for e in fs::read_dir(dir)? {
+    let e: fs::DirEntry = e?;
+    let name: String = e.file_name().to_string_lossy().to_string();
+    if reserved(&name) { continue; }
+
+    let metadata: fs::Metadata = match e.metadata() {
+      Ok(m) => m,
+      Err(_) => continue,  // removed while listing
+    };
+    let mtime: u64 = metadata.modified().ok()
+      .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+      .map_or(0, |d| d.as_secs());
+
+    entries.push(Entry { name, size: if metadata.is_dir() { 0 } else { metadata.len() }, mtime, dir: metadata.is_dir() });
+  }
+
+  entries.sort_by(|a, b| a.name.cmp(&b.name));
+  Ok(entries)
+}
+
+
+/// Encode a listing to be sent like the contents of a file.
+///
+/// Each entry's format is:
+/// <SIZE><MTIME><DIR><NAME LENGTH><NAME>
+pub fn encode(entries: &[Entry]) -> Vec<u8> {
+  let mut buf: Vec<u8> = Vec::new();
+
+  for e in entries {
+    let name: &[u8] = e.name.as_bytes();
+    let name: &[u8] = &name[..name.len().min(u16::MAX as usize)];
+
+    buf.extend_from_slice(&e.size.to_be_bytes());
+    buf.extend_from_slice(&e.mtime.to_be_bytes());
+    buf.push(e.dir as u8);
+    buf.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    buf.extend_from_slice(name);
+  }
+
+  buf
+}
+
+
+/// Decode a listing received from the datastore.
+pub fn decode(buf: &[u8]) -> Result<Vec<Entry>, Serr> {
+  let mut entries: Vec<Entry> = Vec::new();
+  let mut rest: &[u8] = buf;
+
+  while !rest.is_empty() {
+    if rest.len() < FIELDS_LEN {
+      return Err(Serr::SERVER(format!("listing ends with a truncated entry of {} bytes", rest.len())));
+    }
+
+    let size: u64 = u64::from_be_bytes(rest[..8].try_into().unwrap_or_default());
+    let mtime: u64 = u64::from_be_bytes(rest[8..16].try_into().unwrap_or_default());
+    let dir: bool = rest[16] != 0;
+    let name_len: usize = u16::from_be_bytes([rest[17], rest[18]]) as usize;
+
+    let name: &[u8] = match rest.get(FIELDS_LEN..FIELDS_LEN + name_len) {
+      Some(n) => n,
+      None => return Err(Serr::SERVER("listing ends with a truncated name".to_string())),
+    };
+
+    entries.push(Entry { name: String::from_utf8_lossy(name).to_string(), size, mtime, dir });
+    rest = &rest[FIELDS_LEN + name_len..];
+  }
+
+  Ok(entries)
+}
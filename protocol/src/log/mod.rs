@@ -0,0 +1,81 @@
+use std::{str::FromStr, sync::atomic::{AtomicU8, Ordering}};
+
+/// Most verbose level printed, INFO by default
+static LEVEL: AtomicU8 = AtomicU8::new(Level::INFO as u8);
+
+
+/// Enum of the levels messages are printed at,
+/// from least to most verbose.
+#[derive(Clone, Copy, Debug)]
+#[derive(PartialEq, PartialOrd)]
+pub enum Level {
+  OFF,
+  ERROR,
+  WARN,
+  INFO,
+  DEBUG,
+}
+
+
+impl FromStr for Level {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Level, String> {
+    match s.to_ascii_lowercase().as_str() {
+      "off" => Ok(Level::OFF),
+      "error" => Ok(Level::ERROR),
+      "warn" => Ok(Level::WARN),
+      "info" => Ok(Level::INFO),
+      "debug" => Ok(Level::DEBUG),
+      _ => Err(format!("unknown log level {}, expected off, error, warn, info or debug", s)),
+    }
+  }
+}
+
+
+/// Set the most verbose level printed.
+pub fn set_level(level: Level) {
+  LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+
+/// Determine if messages of the provided level are printed.
+pub fn enabled(level: Level) -> bool {
+  level != Level::OFF && level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+
+/// Print an error to stderr.
+#[macro_export]
+macro_rules! error {
+  ($($arg:tt)*) => {
+    if $crate::log::enabled($crate::log::Level::ERROR) { eprintln!($($arg)*); }
+  };
+}
+
+
+/// Print a warning to stderr.
+#[macro_export]
+macro_rules! warn {
+  ($($arg:tt)*) => {
+    if $crate::log::enabled($crate::log::Level::WARN) { eprintln!($($arg)*); }
+  };
+}
+
+
+/// Print progress to stdout.
+#[macro_export]
+macro_rules! info {
+  ($($arg:tt)*) => {
+    if $crate::log::enabled($crate::log::Level::INFO) { println!($($arg)*); }
+  };
+}
+
+
+/// Print details of the protocol to stderr.
+#[macro_export]
+macro_rules! debug {
+  ($($arg:tt)*) => {
+    if $crate::log::enabled($crate::log::Level::DEBUG) { eprintln!($($arg)*); }
+  };
+}
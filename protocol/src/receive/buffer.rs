@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+
+use super::Sink;
+use crate::{digest::{Sha256, DIGEST_LEN}, valid_checksum, Serr, MAX_SACKS, get_seq, get_conn_id, DATA, BODY_START, ReadData};
+
+
+pub struct Buf<S: Sink> {
+  filename: String,
+  file: S,
+  id: u32,  // connection ID
+  size: u64,  // number of bytes of file
+  start: u64,  // next expected byte
+  segments: BTreeMap<u64, Vec<u8>>,  // data received past the next expected byte, by sequence number/byte position
+  window: usize,  // most segments buffered
+  digest: Sha256,  // digest of the data saved to file
+}
+
+
+impl<S: Sink> Buf<S> {
+  /// Create new buffer saving to the sink, buffering at most
+  /// the provided number of segments.
+  pub fn new(file: S, f: &str, s: u64, id: u32, window: usize) -> Buf<S> {
+    Buf { filename: f.to_string(), file, id, size: s, start: 0, segments: BTreeMap::new(), window: window.max(1), digest: Sha256::new(), }
+  }
+
+
+  /// Save data that's in sequential order to disk.
+  /// Returns the next expected byte.
+  pub fn save_read_data(&mut self) -> Result<u64, Serr> {
+    while let Some(entry) = self.segments.first_entry() {
+      let seq: u64 = *entry.key();
+      if seq > self.start { break; }
+
+      // segments may overlap when resent at a smaller size
+      let buf: Vec<u8> = entry.remove();
+      let end: u64 = seq + buf.len() as u64;
+      if end <= self.start { continue; }
+      let data: &[u8] = &buf[(self.start - seq) as usize..];
+
+      // save data and update num_bytes_saved
+      match self.file.write_all(data) {
+        Ok(_) => (),
+        Err(_) => return Result::Err(Serr::SERVER(format!("Unable to write seq {} to {}", seq, self.filename))),
+      };
+      self.digest.update(data);
+
+      // update counts
+      self.start = end;
+    }
+
+    Ok(self.start)
+  }
+
+
+  /// Commit the saved data once the transfer completed.
+  pub fn commit(&mut self) -> Result<(), Serr> {
+    match self.file.commit() {
+      Ok(_) => Ok(()),
+      Err(e) => Err(Serr::SERVER(format!("Unable to commit {}:\n{}", self.filename, e))),
+    }
+  }
+
+
+  /// Discard the saved data once the transfer failed its digest check.
+  pub fn abort(&mut self) {
+    self.file.abort();
+  }
+
+
+  /// Determine if the sender may only finish once the data was committed.
+  pub fn durable(&self) -> bool {
+    self.file.durable()
+  }
+
+
+  /// Add a DATA packet of this connection to the window.
+  pub fn add(&mut self, buf: &[u8]) -> Result<ReadData, Serr> {
+    let seq: u64;
+    let amt: usize;
+
+    // ensure data flag set
+    if buf[0] != DATA { crate::debug!("expected DATA flag ({}) got {}", DATA, buf[0]); return Ok(ReadData::MORE); }
+
+    // drop late packet from an earlier transfer
+    if get_conn_id(buf)? != self.id { return Ok(ReadData::MORE); }
+
+    // drop corrupted packet
+    if !valid_checksum(buf)? { crate::debug!("Dropping DATA packet with invalid checksum"); return Ok(ReadData::MORE); }
+    seq = get_seq(buf)?;
+
+    // drop delayed paket, or padding past the end of the file
+    if seq >= self.size { return Ok(ReadData::MORE); }
+    amt = (buf.len() - BODY_START).min((self.size - seq).try_into().unwrap_or(usize::MAX));
+    if seq + amt as u64 <= self.start { return Ok(ReadData::MORE); }
+
+    // data already at index -> drop packet, the next expected byte is
+    // always taken, but data past it only while the window has room
+    if self.segments.contains_key(&seq) { return Ok(ReadData::MORE); }
+    if seq > self.start && self.segments.len() >= self.window { return Ok(ReadData::MORE); }
+
+    // add to data window
+    self.segments.insert(seq, buf[BODY_START..BODY_START + amt].to_vec());
+
+    // check if this is last packet
+    if self.is_complete() { return Ok(ReadData::DONE); }
+
+    Ok(ReadData::MORE)
+  }
+
+
+  /// Determine if every byte of the file was received.
+  pub fn is_complete(&self) -> bool {
+    let mut end: u64 = self.start;
+
+    for (seq, data) in self.segments.iter() {
+      if *seq > end { break; }
+      end = end.max(seq + data.len() as u64);
+    }
+
+    end >= self.size
+  }
+
+
+  /// Get the number of packets that can still be buffered past the next
+  /// expected byte, besides those already buffered.
+  pub fn window(&self) -> usize {
+    self.window.saturating_sub(self.segments.len())
+  }
+
+
+  /// Get the ranges of bytes received past the next expected byte.
+  pub fn sack(&self) -> Vec<(u64, u64)> {
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+
+    for (seq, data) in self.segments.iter() {
+      let end: u64 = seq + data.len() as u64;
+      if let Some(r) = ranges.last_mut().filter(|r| *seq <= r.1) {
+        r.1 = r.1.max(end);
+      } else if ranges.len() < MAX_SACKS {
+        ranges.push((*seq, end));
+      } else {
+        break;
+      }
+    }
+
+    ranges
+  }
+
+
+  /// Get the digest of the data saved to file so far.
+  pub fn digest(&self) -> [u8; DIGEST_LEN] {
+    self.digest.clone().finish()
+  }
+
+
+  pub fn _print_seq(&self) {
+    for (seq, data) in self.segments.iter() {
+      println!("{:?}", (seq, data.len()));
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::create_pkt;
+
+
+  #[test]
+  fn next_expected_byte_is_taken_when_window_is_full() {
+    let mut saved: Vec<u8> = Vec::new();
+    let mut buf: Buf<&mut Vec<u8>> = Buf::new(&mut saved, "f", 8, 1, 2);
+
+    assert_eq!(buf.add(&create_pkt(DATA, 1, 4, b"ef")).unwrap(), ReadData::MORE);
+    assert_eq!(buf.add(&create_pkt(DATA, 1, 6, b"gh")).unwrap(), ReadData::MORE);
+    assert_eq!(buf.window(), 0);
+
+    // out of order data past a full window is dropped
+    assert_eq!(buf.add(&create_pkt(DATA, 1, 2, b"cd")).unwrap(), ReadData::MORE);
+    assert_eq!(buf.sack(), vec![(4, 8)]);
+
+    assert_eq!(buf.add(&create_pkt(DATA, 1, 0, b"ab")).unwrap(), ReadData::MORE);
+    assert_eq!(buf.save_read_data().unwrap(), 2);
+    assert_eq!(buf.add(&create_pkt(DATA, 1, 2, b"cd")).unwrap(), ReadData::DONE);
+    assert_eq!(buf.save_read_data().unwrap(), 8);
+    drop(buf);
+    assert_eq!(saved, b"abcdefgh");
+  }
+}
@@ -0,0 +1,163 @@
+mod buffer;
+
+use std::{fs::File, io::{self, Write}, net::TcpStream, time::Instant};
+
+use crate::{MAX_MTU, HEADER_LEN, Serr, ReadData, get_conn_id, connection::Connection, get_digest, fin_body, digest::DIGEST_LEN};
+
+use self::buffer::Buf;
+
+use super::{create_ack, create_header, linger, ACK_EVERY, DATA, create_pkt, FIN, FLAG_404, FLAG_500};
+
+/// Where received data is saved.
+pub trait Sink: Write {
+  /// Make the saved data visible once all of it was received and its
+  /// digest matched the sender's, before the FIN lets the peer finish.
+  fn commit(&mut self) -> io::Result<()> {
+    self.flush()
+  }
+
+
+  /// Determine if the sender may only finish once the data was committed,
+  /// so the FIN is sent again for as long as the sender may not have it.
+  fn durable(&self) -> bool {
+    false
+  }
+
+
+  /// Discard the data saved by this transfer once its digest didn't match
+  /// the sender's, so it's never committed.
+  fn abort(&mut self) {}
+}
+
+
+impl Sink for File {}
+
+
+/// Data written to a stream is sent as it's received, and writes
+/// block while the stream's peer falls behind, holding back ACKs.
+impl Sink for &TcpStream {}
+
+
+/// Data small enough to be held in memory, e.g. a listing.
+impl Sink for &mut Vec<u8> {}
+
+
+/// Receive data via UDP over the connection, saving it to the sink.
+/// If all data read successfully and was committed, returns Ok(())
+pub fn receive<S: Sink>(conn: &mut Connection, file: S, filename: &str, size: u64) -> Result<(), Serr> {
+  let mut amt: usize;
+  let mut buf: [u8; MAX_MTU] = [0; MAX_MTU];
+  let mut pkt: &[u8];
+  let mut data_buf: Buf<S> = Buf::new(file, filename, size, conn.id, conn.limits.window);
+  let mut ack_seq: u64;
+  let mut first_ack: Option<Instant> = Some(Instant::now());  // times the round trip to the first DATA
+
+  loop {
+    // save sequential data read so far, send ACK, wait for data to be sent
+    ack_seq = data_buf.save_read_data()?;
+    ack(conn, ack_seq, data_buf.window(), &data_buf.sack())?;
+
+    // nothing to receive for an empty file
+    if data_buf.is_complete() { return done(conn, ack_seq, filename, data_buf); }
+
+    // add new data to window while data is read in
+    for _ in 0..ACK_EVERY {
+      amt = match conn.recv(&mut buf) { // read in data
+        Ok(i) => i,
+        Err(_) => {  // packet loss, re-ACK rather than waiting out the window
+          conn.timed_out()?;
+          conn.rtt.backoff();
+          first_ack = None;
+          break;
+        },
+      };
+      // drop datagrams too short to hold a header
+      if amt < HEADER_LEN { continue; }
+      pkt = &buf[..amt];
+
+      if (pkt[0] == FLAG_404 || pkt[0] == FLAG_500) && get_conn_id(pkt)? == conn.id {
+        return Err(Serr::SERVER(format!("Peer had unrecoverable error while sending {}", filename)));
+      }
+
+      // ignore stale requests, sender hasn't received ack 0
+      if pkt[0] < DATA { continue; }
+
+      if get_conn_id(pkt)? == conn.id {
+        if let Some(sent) = first_ack.take() { conn.rtt.sample(sent.elapsed()); }
+        conn.rtt.reset_backoff();
+        conn.answered();
+      }
+
+      match data_buf.add(pkt)? {  // add data to window
+        ReadData::MORE => (),
+        ReadData::DONE => {  // add data received, done
+          ack_seq = data_buf.save_read_data()?;
+          return done(conn, ack_seq, filename, data_buf);
+        },
+      };
+    }
+  }
+}
+
+
+/// Terminate connection.
+///
+/// ACKs all data until the sender answers with a FIN carrying the digest
+/// of the sent file, then commits the data if it matches the digest of the
+/// received file and answers with a FIN carrying it, or with an error if not.
+fn done<S: Sink>(conn: &mut Connection, seq: u64, filename: &str, mut data_buf: Buf<S>) -> Result<(), Serr> {
+  let digest: [u8; DIGEST_LEN] = data_buf.digest();
+  let mut buf: [u8; MAX_MTU];
+  let mut pkt: &[u8];
+  let mut amt: usize;
+
+  loop {
+    ack(conn, seq, data_buf.window(), &[])?;
+
+    buf = [0; MAX_MTU];
+    amt = match conn.recv(&mut buf) {
+      Ok(i) => i,
+      Err(_) => { conn.timed_out()?; conn.rtt.backoff(); continue },
+    };
+
+    // drop truncated datagrams and stale packets from an earlier transfer,
+    // late DATA is acked again
+    pkt = &buf[..amt];
+    if amt < HEADER_LEN || get_conn_id(pkt)? != conn.id { continue; }
+
+    if pkt[0] == FLAG_500 || pkt[0] == FLAG_404 {
+      return Err(Serr::SERVER(format!("Received error flag {} while terminating", pkt[0])));
+    }
+    if pkt[0] != FIN { continue; }
+    conn.answered();
+
+    // the sender keeps sending its FIN until it's answered, the sink
+    // is released first so another transfer may use it meanwhile
+    if get_digest(pkt)? != digest {
+      data_buf.abort();
+      drop(data_buf);
+      linger(conn, &create_header(FLAG_500, conn.id, seq));
+      return Err(Serr::SERVER(format!("Digest of {} received does not match digest sent by peer", filename)));
+    }
+    data_buf.commit()?;
+
+    let fin: Vec<u8> = create_pkt(FIN, conn.id, seq, &fin_body(filename, &digest)?);
+    match data_buf.durable() {
+      true => { drop(data_buf); linger(conn, &fin); },
+      false => { let _ = conn.send(&fin); },
+    }
+    return Ok(());
+  }
+}
+
+
+/// ACK's the next smallest expected byte, advertising the
+/// number of packets there's still room for past it, the largest
+/// datagram accepted, and which bytes past it were received.
+pub fn ack(conn: &Connection, seq: u64, window: usize, sack: &[(u64, u64)]) -> Result<(), Serr> {
+  let buf: Vec<u8> = create_ack(conn.id, seq, window, conn.mtu, sack);
+  match conn.send(&buf) {
+    Ok(_) => Ok(()),
+    Err(_) => Err(Serr::SERVER("UDP socket is not connected, cannot read from UDP socket".to_string())),
+  }
+}
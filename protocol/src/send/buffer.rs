@@ -0,0 +1,288 @@
+use std::{io::{Read, ErrorKind}, collections::VecDeque, time::{Duration, Instant}};
+
+use crate::{digest::{Sha256, DIGEST_LEN}, connection::{Connection, rtt::Rtt}, INITIAL_WINDOW, HEADER_LEN, DATA, create_pkt, ACK, get_seq, get_conn_id, get_window, get_sack, valid_checksum, Serr};
+
+use super::{congestion::Congestion, pmtu::Pmtu};
+
+
+/// A segment of the file held in the window until it's acked.
+struct Segment {
+  seq: u64,  // sequence number/byte position
+  data: Vec<u8>,
+  sent: Option<Instant>,  // when last sent, None if never sent
+  retransmitted: bool,  // if sent more than once, its ACK can't time a round trip
+  sacked: bool,  // if selectively acked, it's never retransmitted
+  probe: bool,  // if larger than the path is known to carry
+}
+
+
+impl Segment {
+  /// Get the sequence number past the segment's last byte.
+  fn end(&self) -> u64 {
+    self.seq + self.data.len() as u64
+  }
+}
+
+
+pub struct Buf<R: Read> {
+  file: R,
+  pub filename: String,
+  id: u32,  // connection ID
+  next: u64,  // sequence number of the next segment read from file
+  window: VecDeque<Segment>,  // unacked segments, in order
+  congestion: Congestion,  // limits the size of the window
+  pmtu: Pmtu,  // limits the size of segments
+  digest: Sha256,  // digest of the data read from file
+}
+
+
+impl<R: Read> Buf<R> {
+  /// Create new buffer reading from the source, sending datagrams
+  /// no larger than the provided size.
+  ///
+  /// Fills windows with initial data
+  pub fn new(f: R, filename: &str, id: u32, mtu: usize) -> Result<Buf<R>, Serr> {
+    let mut b: Buf<R> = Buf { file: f, filename: filename.to_string(), id, next: 0, window: VecDeque::with_capacity(INITIAL_WINDOW), congestion: Congestion::new(), pmtu: Pmtu::new(mtu), digest: Sha256::new(), };
+    b.fill_window()?;
+    Ok(b)
+  }
+
+
+  /// Fill window with data from file.
+  fn fill_window(&mut self) -> Result<(), Serr> {
+    let mut data_buf: Vec<u8>;
+    let mut amt: usize;
+    let mut size: usize;
+    let mut probe: bool;
+
+    while self.window.len() < self.congestion.window() {
+      // fetch data
+      (size, probe) = self.pmtu.next();
+      data_buf = vec![0; size - HEADER_LEN];
+      amt = self.read(&mut data_buf)?;
+      data_buf.truncate(amt);
+
+      // a segment no larger than the path carries doesn't probe it
+      if probe && amt + HEADER_LEN <= self.pmtu.mtu() {
+        self.pmtu.cancel();
+        probe = false;
+      }
+
+      // check amount read
+      if amt == 0 { return Ok(()); }  // read all data from file
+
+      // write to window
+      self.digest.update(&data_buf);
+      self.window.push_back(Segment { seq: self.next, data: data_buf, sent: None, retransmitted: false, sacked: false, probe });
+      self.next += amt as u64;
+    }
+
+    Ok(())
+  }
+
+
+  /// Read from file until the buffer is full or the file ends.
+  ///
+  /// A source ending before the length it was sent with, e.g. a body
+  /// cut short by a client, is malformed.
+  fn read(&mut self, buf: &mut [u8]) -> Result<usize, Serr> {
+    let mut total: usize = 0;
+
+    while total < buf.len() {
+      match self.file.read(&mut buf[total..]) {
+        Ok(0) => break,
+        Ok(amt) => total += amt,
+        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Err(Serr::BAD(format!("{} ended early: {}", self.filename, e))),
+        Err(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock => return Err(Serr::BAD(format!("{} stalled: {}", self.filename, e))),
+        Err(_) => return Err(Serr::SERVER(format!("Could not read from {}", self.filename))),
+      }
+    }
+
+    Ok(total)
+  }
+
+
+  /// Determine if all data sent.
+  pub fn is_done(&self) -> bool {
+    self.window.is_empty()
+  }
+
+
+  /// Get the sequence number past the last byte read from file.
+  pub fn end(&self) -> u64 {
+    self.next
+  }
+
+
+  /// Get the digest of the data read from file so far.
+  pub fn digest(&self) -> [u8; DIGEST_LEN] {
+    self.digest.clone().finish()
+  }
+
+
+  /// Send the data in the window that hasn't been sent yet.
+  pub fn send(&mut self, conn: &Connection) {
+    let window: usize = self.congestion.window();
+
+    for seg in self.window.iter_mut().take(window).filter(|s| s.sent.is_none()) {
+      let _ = conn.send(&create_pkt(DATA, self.id, seg.seq, &seg.data));
+      seg.sent = Some(Instant::now());
+    }
+  }
+
+
+  /// Resend the data in the window the receiver is missing after
+  /// the oldest segment timed out.
+  ///
+  /// The window collapses, so segments are resent as it grows back.
+  pub fn retransmit(&mut self, conn: &Connection) {
+    let in_flight: usize = self.window.iter().filter(|s| s.sent.is_some() && !s.sacked).count();
+    if self.split_probes(u64::MAX) < in_flight { self.congestion.timeout(in_flight); }
+
+    for seg in self.window.iter_mut().filter(|s| !s.sacked) {
+      seg.retransmitted |= seg.sent.is_some();
+      seg.sent = None;
+    }
+
+    self.send(conn);
+  }
+
+
+  /// Resend the segments the receiver is missing after duplicate ACKs,
+  /// being those sent before a segment it selectively acked.
+  pub fn fast_retransmit(&mut self, conn: &Connection) {
+    let in_flight: usize = self.window.iter().filter(|s| s.sent.is_some() && !s.sacked).count();
+    let last_sacked: u64 = self.window.iter().filter(|s| s.sacked).map(|s| s.seq).max().unwrap_or(0);
+
+    // the oldest segment is always missing, as it's the one being acked
+    let missing: usize = self.window.iter().enumerate().filter(|(i, s)| !s.sacked && s.sent.is_some() && (*i == 0 || s.seq <= last_sacked)).count();
+    if self.split_probes(last_sacked.max(self.window.front().map_or(0, |s| s.seq))) < missing {
+      self.congestion.fast_retransmit(in_flight);
+    }
+
+    for (i, seg) in self.window.iter_mut().enumerate() {
+      if seg.sacked || seg.sent.is_none() || (i > 0 && seg.seq > last_sacked) { continue; }
+
+      let _ = conn.send(&create_pkt(DATA, self.id, seg.seq, &seg.data));
+      seg.retransmitted = true;
+      seg.sent = Some(Instant::now());
+    }
+  }
+
+
+  /// Split the missing probes sent up to the provided sequence number
+  /// into segments the path is known to carry, as they were likely
+  /// lost for being too large rather than to congestion.
+  ///
+  /// Returns the number of probes split.
+  fn split_probes(&mut self, last: u64) -> usize {
+    let mut split: usize = 0;
+    let size: usize = self.pmtu.mtu() - HEADER_LEN;
+    let mut window: VecDeque<Segment> = VecDeque::with_capacity(self.window.len());
+
+    for seg in std::mem::take(&mut self.window) {
+      if !seg.probe || seg.sacked || seg.sent.is_none() || seg.seq > last {
+        window.push_back(seg);
+        continue;
+      }
+
+      self.pmtu.lost();
+      split += 1;
+      for (i, chunk) in seg.data.chunks(size).enumerate() {
+        window.push_back(Segment { seq: seg.seq + (i * size) as u64, data: chunk.to_vec(), sent: seg.sent, retransmitted: true, sacked: false, probe: false });
+      }
+    }
+
+    self.window = window;
+    split
+  }
+
+
+  /// Get the time left before the oldest unacked segment
+  /// should be retransmitted.
+  pub fn timeout(&self, rto: Duration) -> Duration {
+    match self.window.front().and_then(|s| s.sent) {
+      Some(sent) => (sent + rto).saturating_duration_since(Instant::now()),
+      None => rto,
+    }
+  }
+
+
+  /// Slide the window over with respect to ACK received, timing
+  /// the round trip of the acked segments and growing the window.
+  ///
+  /// Returns whether the ACK belonged to this connection.
+  pub fn adjust(&mut self, buf: &[u8], rtt: &mut Rtt) -> Result<bool, Serr> {
+    let seq: u64;
+    let mut sample: Option<Duration> = None;
+    let mut acked: usize = 0;
+
+    // ensure ack received
+    if buf[0] < ACK { crate::debug!("Received non-ACK (ACK types > {}), instead {}", ACK, buf[0]); return Ok(false); }
+
+    // drop late ACKs from an earlier transfer
+    if get_conn_id(buf)? != self.id { return Ok(false); }
+
+    // shift windows to index
+    seq = get_seq(buf)?;
+
+    // ACKs carry the receiver's window and selective acks, FINs don't
+    if buf[0] == ACK {
+      if !valid_checksum(buf)? { crate::debug!("Dropping ACK with invalid checksum"); return Ok(false); }
+      sample = self.sack(&get_sack(buf)?);
+    }
+
+    while let Some(seg) = self.window.front() {
+      if seg.end() > seq { break; }
+
+      // segments acked selectively were already timed
+      if let (Some(sent), false, false) = (seg.sent, seg.retransmitted, seg.sacked) {
+        sample = Some(sent.elapsed());
+      }
+      if seg.probe && !seg.sacked { self.pmtu.acked(seg.data.len() + HEADER_LEN); }
+      self.window.pop_front();
+      acked += 1;
+    }
+
+    if let Some(r) = sample { rtt.sample(r); }
+
+    // the receiver has room for the segments it advertised besides those it
+    // buffered, which are the segments left in the window selectively acked
+    if buf[0] == ACK {
+      self.congestion.advertise(get_window(buf)? + self.window.iter().filter(|s| s.sacked).count());
+    }
+    if acked > 0 { self.congestion.acked(acked); }
+
+    self.fill_window()?;
+    Ok(true)
+  }
+
+
+  /// Mark the segments the receiver received past the acked byte.
+  ///
+  /// Returns the round trip of the newest segment selectively
+  /// acked for the first time, if it wasn't retransmitted.
+  fn sack(&mut self, ranges: &[(u64, u64)]) -> Option<Duration> {
+    let mut sample: Option<Duration> = None;
+
+    for seg in self.window.iter_mut().filter(|s| !s.sacked) {
+      if ranges.iter().any(|(start, end)| *start <= seg.seq && seg.end() <= *end) {
+        seg.sacked = true;
+        if seg.probe { self.pmtu.acked(seg.data.len() + HEADER_LEN); }
+        if let (Some(sent), false) = (seg.sent, seg.retransmitted) {
+          sample = Some(sent.elapsed());
+        }
+      }
+    }
+
+    sample
+  }
+
+
+  pub fn _print_indicies(&self) {
+    for seg in self.window.iter() {
+      println!("{:?}", (seg.seq, seg.data.len(), seg.sent, seg.retransmitted, seg.sacked, seg.probe));
+    }
+  }
+}
@@ -0,0 +1,77 @@
+use crate::{INITIAL_WINDOW, RECV_WINDOW};
+
+/// Smallest slow start threshold, in packets
+const MIN_SSTHRESH: usize = 2;
+
+
+/// Limits the number of packets in flight with slow start and
+/// additive increase/multiplicative decrease, as described by RFC 5681.
+pub struct Congestion {
+  cwnd: usize,  // congestion window, in packets
+  ssthresh: usize,  // window size where slow start ends
+  acked: usize,  // packets acked since the window last grew during congestion avoidance
+  rwnd: usize,  // window advertised by the receiver
+}
+
+
+impl Default for Congestion {
+  fn default() -> Self {
+    Congestion::new()
+  }
+}
+
+
+impl Congestion {
+  /// Create a window in slow start.
+  pub fn new() -> Congestion {
+    Congestion { cwnd: INITIAL_WINDOW, ssthresh: RECV_WINDOW, acked: 0, rwnd: INITIAL_WINDOW }
+  }
+
+
+  /// Grow the window after packets were acked, doubling it every round
+  /// trip during slow start and growing it by a packet every round trip after.
+  ///
+  /// The window doesn't grow past what the receiver advertised.
+  pub fn acked(&mut self, amt: usize) {
+    if self.cwnd < self.ssthresh {
+      self.cwnd += amt;
+    } else {
+      self.acked += amt;
+      while self.acked >= self.cwnd {
+        self.acked -= self.cwnd;
+        self.cwnd += 1;
+      }
+    }
+
+    self.cwnd = self.cwnd.min(self.rwnd);
+  }
+
+
+  /// Halve the window after duplicate ACKs reported a lost packet.
+  pub fn fast_retransmit(&mut self, in_flight: usize) {
+    self.ssthresh = (in_flight / 2).max(MIN_SSTHRESH);
+    self.cwnd = self.ssthresh;
+    self.acked = 0;
+  }
+
+
+  /// Collapse the window after a packet timed out, slow starting
+  /// back up to half of the packets that were in flight.
+  pub fn timeout(&mut self, in_flight: usize) {
+    self.ssthresh = (in_flight / 2).max(MIN_SSTHRESH);
+    self.cwnd = 1;
+    self.acked = 0;
+  }
+
+
+  /// Update the window advertised by the receiver.
+  pub fn advertise(&mut self, rwnd: usize) {
+    self.rwnd = rwnd.max(1);
+  }
+
+
+  /// Get the number of packets that may be in flight.
+  pub fn window(&self) -> usize {
+    self.cwnd.min(self.rwnd)
+  }
+}
@@ -0,0 +1,98 @@
+mod buffer;
+mod congestion;
+mod pmtu;
+
+use std::io::Read;
+
+use crate::{MAX_MTU, HEADER_LEN, Serr, get_conn_id, get_seq, get_digest, create_pkt, fin_body, digest::DIGEST_LEN, connection::Connection};
+
+use self::buffer::Buf;
+
+use super::{ACK, FIN, FLAG_404, FLAG_500};
+
+/// Number of duplicate ACKs signaling a lost packet
+const DUP_ACKS: usize = 3;
+
+
+/// Send the data read from the provided source via UDP over the connection.
+///
+/// The source is read as the window has room, so data is sent as it becomes available.
+pub fn send<R: Read>(conn: &mut Connection, file: R, filename: &str) -> Result<(), Serr> {
+  let mut buf: [u8; MAX_MTU] = [0; MAX_MTU];
+  let mut pkt: &[u8];
+  let mut data_buf: Buf<R> = Buf::new(file, filename, conn.id, conn.mtu)?;
+  let mut amt: usize;
+  let mut last_ack: u64 = 0;  // last cumulative ACK received
+  let mut dup_acks: usize = 0;  // number of times it was received again
+  let mut fin: Option<Vec<u8>> = None;  // FIN carrying the digest, once all data was acked
+
+  loop {
+    // send data new to the window, and the digest once the receiver
+    // has all of it, so it can compare digests before committing
+    data_buf.send(conn);
+    if data_buf.is_done() && fin.is_none() {
+      let f: Vec<u8> = create_pkt(FIN, conn.id, data_buf.end(), &fin_body(filename, &data_buf.digest())?);
+      let _ = conn.send(&f);
+      fin = Some(f);
+    }
+
+    // read ack, until the oldest unacked segment times out
+    amt = match conn.recv_timeout(&mut buf, data_buf.timeout(conn.rtt.rto())) {
+      Ok(i) => i,
+      Err(_) => {  // packet lost, or the receiver's FIN is late
+        if data_buf.is_done() || data_buf.timeout(conn.rtt.rto()).is_zero() {
+          conn.timed_out()?;
+          conn.rtt.backoff();
+          match &fin {
+            Some(f) => { let _ = conn.send(f); },
+            None => data_buf.retransmit(conn),
+          }
+        }
+        continue
+      },
+    };
+
+    if amt >= HEADER_LEN {  // adjust window
+      pkt = &buf[..amt];
+      if (pkt[0] == FLAG_404 || pkt[0] == FLAG_500) && get_conn_id(pkt)? == conn.id {
+        return Err(Serr::SERVER(format!("Peer had unrecoverable error while receiving {}", filename)));
+      }
+
+      // ignore stale requests
+      if pkt[0] < ACK { continue; }
+
+      if !data_buf.adjust(pkt, &mut conn.rtt)? { continue; }
+      conn.answered();
+      if pkt[0] == FIN { return terminate(&data_buf, pkt); }
+
+      // the receiver acks every packet it gets past a lost one with the
+      // same sequence number, so retransmit without waiting for a timeout
+      if get_seq(pkt)? != last_ack {
+        last_ack = get_seq(pkt)?;
+        dup_acks = 0;
+      } else if let Some(f) = &fin {  // the receiver acks all data again until it has the digest
+        let _ = conn.send(f);
+      } else if !data_buf.is_done() {
+        dup_acks += 1;
+        if dup_acks == DUP_ACKS { data_buf.fast_retransmit(conn); }
+      }
+    }
+  }
+}
+
+/// Terminate connection once the receiver answered the FIN carrying the
+/// digest of the sent file, having committed the data if the digest of
+/// the received file matched.
+fn terminate<R: Read>(data_buf: &Buf<R>, buf: &[u8]) -> Result<(), Serr> {
+  let digest: [u8; DIGEST_LEN] = data_buf.digest();
+
+  if !data_buf.is_done() {
+    return Err(Serr::SERVER("Received FIN before all data was sent".to_string()));
+  }
+  if get_digest(buf)? != digest {
+    return Err(Serr::SERVER(format!("Digest of {} received by peer does not match digest sent", data_buf.filename)));
+  }
+
+  crate::info!("Successfully sent {}", data_buf.filename);
+  Ok(())
+}
@@ -0,0 +1,79 @@
+use crate::{MTU, MIN_MTU, MAX_MTU, UDP_H_LEN, IP_H_LEN};
+
+/// FDDI MTU in bytes
+const FDDI_MTU: usize = 4352;
+
+/// Datagram sizes probed, being the largest datagrams over
+/// Ethernet, FDDI and jumbo frames
+const PLATEAUS: [usize; 3] = [MTU, FDDI_MTU - UDP_H_LEN - IP_H_LEN, MAX_MTU];
+
+/// Number of probes of a size lost before the path is assumed
+/// not to carry it
+const MAX_PROBES: usize = 2;
+
+
+/// Searches for the largest datagram the path carries by sending
+/// larger DATA packets as probes, as described by RFC 8899.
+pub struct Pmtu {
+  mtu: usize,  // largest datagram known to reach the receiver
+  max: usize,  // largest datagram both ends accept
+  probe: Option<usize>,  // size of the probe in flight
+  lost: usize,  // number of probes of the next size lost
+}
+
+
+impl Pmtu {
+  /// Start searching from the size every path carries,
+  /// up to the provided size.
+  pub fn new(max: usize) -> Pmtu {
+    Pmtu { mtu: MIN_MTU, max: max.max(MIN_MTU), probe: None, lost: 0 }
+  }
+
+
+  /// Get the size of the next datagram to send, and whether it's a probe.
+  ///
+  /// Only one probe is in flight at a time.
+  pub fn next(&mut self) -> (usize, bool) {
+    if self.probe.is_some() { return (self.mtu, false); }
+
+    match PLATEAUS.iter().map(|p| (*p).min(self.max)).find(|p| *p > self.mtu) {
+      Some(size) => {
+        self.probe = Some(size);
+        (size, true)
+      },
+      None => (self.mtu, false),
+    }
+  }
+
+
+  /// Give up on a probe that wasn't sent, as there was nothing left to send.
+  pub fn cancel(&mut self) {
+    self.probe = None;
+  }
+
+
+  /// Raise the size after a probe was acked.
+  pub fn acked(&mut self, size: usize) {
+    self.mtu = self.mtu.max(size);
+    self.probe = None;
+    self.lost = 0;
+  }
+
+
+  /// Record a lost probe, ending the search once a size
+  /// was lost MAX_PROBES times.
+  pub fn lost(&mut self) {
+    self.probe = None;
+    self.lost += 1;
+
+    if self.lost >= MAX_PROBES {
+      self.max = self.mtu;
+    }
+  }
+
+
+  /// Get the largest datagram known to reach the receiver.
+  pub fn mtu(&self) -> usize {
+    self.mtu
+  }
+}
@@ -0,0 +1,586 @@
+use std::{fs::{self, File, Metadata, OpenOptions}, io::{self, ErrorKind, Write}, path::{Component, Path, PathBuf}, process, sync::{Mutex, atomic::{AtomicU64, Ordering}}, time::UNIX_EPOCH};
+
+use crate::{Serr, random, receive::Sink, digest::{Sha256, DIGEST_LEN}};
+
+/// Suffix of the temporary files uploads are written to
+pub const TMP_SUFFIX: &str = ".part";
+
+/// Number of uploads started, keeping temporary files apart
+static UPLOADS: AtomicU64 = AtomicU64::new(0);
+
+/// Suffix of the files resumable uploads keep their partial data in
+pub const PARTIAL_SUFFIX: &str = ".upload";
+
+/// Partial files being appended to, which only one transfer may append to at once
+static APPENDING: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Suffix of the files the digests of stored files are kept in
+pub const DIGEST_SUFFIX: &str = ".digest";
+
+/// Length of the size and modification time a kept digest is valid for
+const DIGEST_KEY_LEN: usize = 8 + 16;
+
+
+/// The directory a server stores files in, which requested
+/// paths may not escape.
+#[derive(Clone, Debug)]
+pub struct Root {
+  dir: PathBuf,  // canonical path of the directory
+}
+
+
+impl Root {
+  /// Store files in the provided directory, creating it if it doesn't exist.
+  pub fn new(dir: &str) -> io::Result<Root> {
+    fs::create_dir_all(dir)?;
+    Ok(Root { dir: fs::canonicalize(dir)? })
+  }
+
+
+  /// Get the path of a file requested by a peer, relative to the root.
+  ///
+  /// Paths that are malformed are rejected as by relative, and paths that
+  /// leave the root through a symlink or name a file the server keeps
+  /// next to stored files are forbidden.
+  pub fn resolve(&self, name: &str) -> Result<PathBuf, Serr> {
+    let path: PathBuf = relative(name)?;
+
+    if path.file_name().is_some_and(|n| reserved(&n.to_string_lossy())) {
+      return Err(Serr::FORBIDDEN(format!("{} is reserved by the server", name)));
+    }
+    self.contain(name, self.dir.join(path))
+  }
+
+
+  /// Get the path of a directory requested by a peer, relative to the root,
+  /// which may be the root itself.
+  ///
+  /// Paths are rejected as by resolve.
+  pub fn resolve_dir(&self, name: &str) -> Result<PathBuf, Serr> {
+    self.contain(name, self.dir.join(relative_dir(name)?))
+  }
+
+
+  /// Ensure the provided path, requested as name, doesn't leave the root through a symlink.
+  fn contain(&self, name: &str, path: PathBuf) -> Result<PathBuf, Serr> {
+    // the deepest part of the path that exists must stay in the root once
+    // symlinks are followed, a dangling symlink could be created anywhere
+    for ancestor in path.ancestors() {
+      if fs::symlink_metadata(ancestor).is_err() { continue; }
+
+      match fs::canonicalize(ancestor) {
+        Ok(c) if c.starts_with(&self.dir) => break,
+        _ => return Err(Serr::FORBIDDEN(format!("{} leaves the storage root", name))),
+      }
+    }
+
+    Ok(path)
+  }
+}
+
+
+/// Get the path of a file requested by a peer, relative to where files are stored.
+///
+/// Paths that are absolute, climb out with .., or name no file are malformed.
+pub fn relative(name: &str) -> Result<PathBuf, Serr> {
+  let path: PathBuf = relative_dir(name)?;
+
+  match path.as_os_str().is_empty() {
+    true => Err(Serr::BAD(format!("{} does not name a file", name))),
+    false => Ok(path),
+  }
+}
+
+
+/// Get the path of a directory requested by a peer, relative to where files
+/// are stored, being empty for where files are stored itself.
+///
+/// Paths that are absolute or climb out with .. are malformed.
+pub fn relative_dir(name: &str) -> Result<PathBuf, Serr> {
+  let mut path: PathBuf = PathBuf::new();
+
+  for component in Path::new(name).components() {
+    match component {
+      Component::CurDir => (),
+      Component::Normal(c) => path.push(c),
+      Component::ParentDir => return Err(Serr::BAD(format!("{} may not contain ..", name))),
+      Component::RootDir | Component::Prefix(_) => return Err(Serr::BAD(format!("{} may not be absolute", name))),
+    }
+  }
+
+  Ok(path)
+}
+
+
+/// Determine if the provided file name is one the server keeps next to
+/// stored files: the temporary file of an upload, the partial file of a
+/// resumable upload, or a kept digest.
+pub fn reserved(name: &str) -> bool {
+  name.strip_prefix('.').is_some_and(|n| [TMP_SUFFIX, PARTIAL_SUFFIX, DIGEST_SUFFIX].iter().any(|s| n.ends_with(s)))
+}
+
+
+/// A file being uploaded, written to a temporary file next to its path
+/// and renamed over it once complete, so readers only ever see the
+/// previous or the new version of the file in full.
+///
+/// The temporary file is removed if the upload isn't committed.
+pub struct Upload {
+  file: File,
+  tmp: PathBuf,  // path of the temporary file
+  path: PathBuf,  // path the file is renamed to
+  digest: Sha256,  // digest of the data written, kept once committed
+  committed: bool,
+}
+
+
+impl Upload {
+  /// Start uploading the file at the provided path.
+  pub fn create(path: &Path) -> io::Result<Upload> {
+    let name: String = match path.file_name() {
+      Some(n) => n.to_string_lossy().to_string(),
+      None => return Err(io::Error::new(ErrorKind::InvalidInput, "upload path does not name a file")),
+    };
+    let tmp: PathBuf = path.with_file_name(format!(".{}.{}.{}{}", name, process::id(), UPLOADS.fetch_add(1, Ordering::Relaxed), TMP_SUFFIX));
+
+    Ok(Upload { file: File::create(&tmp)?, tmp, path: path.to_path_buf(), digest: Sha256::new(), committed: false })
+  }
+}
+
+
+impl Write for Upload {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let amt: usize = self.file.write(buf)?;
+    self.digest.update(&buf[..amt]);
+    Ok(amt)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.file.flush()
+  }
+}
+
+
+impl Sink for Upload {
+  /// Flush the file to disk and rename it over the previous version,
+  /// keeping its digest.
+  fn commit(&mut self) -> io::Result<()> {
+    self.file.sync_all()?;
+    replace(&self.tmp, &self.path)?;
+    self.committed = true;
+
+    // the renamed file keeps the metadata of the temporary one
+    if let Ok(m) = self.file.metadata() {
+      keep_digest(&self.path, &m, &self.digest.clone().finish());
+    }
+    Ok(())
+  }
+
+
+  fn durable(&self) -> bool {
+    true
+  }
+}
+
+
+impl Drop for Upload {
+  fn drop(&mut self) {
+    if !self.committed {
+      let _ = fs::remove_file(&self.tmp);
+    }
+  }
+}
+
+
+/// A resumable upload of a file, whose data is kept in a partial file next
+/// to its path across transfers, and renamed over it once complete.
+///
+/// The partial file of the upload with ID id of a file of size length is
+/// named .<name>.<id as hex>.<length>.upload, and its size is the number
+/// of bytes received so far.
+pub struct Resumable {
+  file: File,
+  partial: PathBuf,  // path of the partial file
+  path: PathBuf,  // path the file is renamed to
+  start: u64,  // bytes received before this transfer
+  offset: u64,  // bytes received so far
+  length: u64,  // size of the whole file
+  committed: bool,
+}
+
+
+impl Resumable {
+  /// Start a resumable upload of a file of the provided size to the provided path.
+  ///
+  /// Returns the upload's ID, which is random so uploads can't be
+  /// continued by guessing it.
+  pub fn create(path: &Path, length: u64) -> io::Result<u64> {
+    let id: u64 = random();
+    let partial: PathBuf = path.with_file_name(format!("{}{:016x}.{}{}", partial_prefix(path)?, id, length, PARTIAL_SUFFIX));
+    File::create(&partial)?;
+
+    // an empty file is complete as soon as it's created
+    if length == 0 { replace(&partial, path)?; }
+    Ok(id)
+  }
+
+
+  /// Get the number of bytes received so far and the size of the whole
+  /// file of the upload with the provided ID to the provided path.
+  pub fn status(path: &Path, id: u64) -> io::Result<(u64, u64)> {
+    let (partial, length) = find(path, id)?;
+    Ok((fs::metadata(partial)?.len(), length))
+  }
+
+
+  /// Abandon the upload with the provided ID to the provided path,
+  /// removing the data received so far.
+  ///
+  /// An upload being continued can't be abandoned until the transfer is over.
+  pub fn remove(path: &Path, id: u64) -> Result<(), Serr> {
+    let name: String = path.display().to_string();
+    let (partial, _) = match find(path, id) {
+      Ok(p) => p,
+      Err(_) => return Err(Serr::DNE(format!("no upload {:016x} of {}", id, name))),
+    };
+
+    let appending = APPENDING.lock().unwrap_or_else(|e| e.into_inner());
+    if appending.contains(&partial) {
+      return Err(Serr::CONFLICT(format!("upload {:016x} of {} is being continued", id, name)));
+    }
+    match fs::remove_file(&partial) {
+      Ok(_) => Ok(()),
+      Err(e) if e.kind() == ErrorKind::NotFound => Err(Serr::DNE(format!("no upload {:016x} of {}", id, name))),
+      Err(e) => Err(Serr::SERVER(format!("Unable to remove upload {:016x} of {}:\n{}", id, name, e))),
+    }
+  }
+
+
+  /// Continue the upload with the provided ID to the provided path with
+  /// data of the provided size, starting at the provided offset.
+  ///
+  /// The offset must be where the upload left off, and no other transfer
+  /// may be continuing the upload.
+  pub fn resume(path: &Path, id: u64, offset: u64, size: u64) -> Result<Resumable, Serr> {
+    let name: String = path.display().to_string();
+    let (partial, length) = match find(path, id) {
+      Ok(p) => p,
+      Err(_) => return Err(Serr::DNE(format!("no upload {:016x} of {}", id, name))),
+    };
+
+    {
+      let mut appending = APPENDING.lock().unwrap_or_else(|e| e.into_inner());
+      if appending.contains(&partial) {
+        return Err(Serr::CONFLICT(format!("upload {:016x} of {} is already being continued", id, name)));
+      }
+      appending.push(partial.clone());
+    }
+
+    // from here on the partial file is released on drop
+    let file: File = match OpenOptions::new().append(true).open(&partial) {
+      Ok(f) => f,
+      Err(e) => { release(&partial); return Err(Serr::SERVER(format!("Unable to open upload {:016x} of {}:\n{}", id, name, e))); },
+    };
+    let start: u64 = file.metadata().map_or(0, |m| m.len());
+    let upload: Resumable = Resumable { file, partial, path: path.to_path_buf(), start, offset: start, length, committed: false };
+
+    if offset != upload.start {
+      return Err(Serr::CONFLICT(format!("upload {:016x} of {} is at byte {}, not {}", id, name, upload.start, offset)));
+    }
+    if offset.saturating_add(size) > length {
+      return Err(Serr::BAD(format!("{} bytes at byte {} exceed the {} bytes of upload {:016x} of {}", size, offset, length, id, name)));
+    }
+    Ok(upload)
+  }
+}
+
+
+impl Resumable {
+  /// Get the size of the whole file.
+  pub fn length(&self) -> u64 {
+    self.length
+  }
+}
+
+
+impl Write for Resumable {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let amt: usize = self.file.write(buf)?;
+    self.offset += amt as u64;
+    Ok(amt)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.file.flush()
+  }
+}
+
+
+impl Sink for Resumable {
+  /// Flush the data received to disk, and rename the file over
+  /// the previous version once all of it was received.
+  fn commit(&mut self) -> io::Result<()> {
+    self.file.sync_all()?;
+    if self.offset < self.length || self.committed { return Ok(()); }
+
+    replace(&self.partial, &self.path)?;
+    self.committed = true;
+    Ok(())
+  }
+
+
+  fn durable(&self) -> bool {
+    true
+  }
+
+
+  /// Cut the partial file back to where this transfer started, as data
+  /// that didn't match the sender's may not be continued.
+  fn abort(&mut self) {
+    if !self.committed && self.file.set_len(self.start).is_ok() {
+      self.offset = self.start;
+    }
+  }
+}
+
+
+impl Drop for Resumable {
+  /// Keep the data received, letting another transfer continue the upload.
+  fn drop(&mut self) {
+    release(&self.partial);
+  }
+}
+
+
+/// Get the digest of the contents of the provided file, opened at the provided path.
+///
+/// The digest is kept in a hidden file next to it for as long as the file keeps
+/// its size and modification time, so a file is only read in full once.
+pub fn file_digest(path: &Path, file: &mut File) -> io::Result<[u8; DIGEST_LEN]> {
+  let metadata: Metadata = file.metadata()?;
+  let key: [u8; DIGEST_KEY_LEN] = digest_key(&metadata);
+
+  if let Ok(kept) = fs::read(digest_path(path)?) {
+    if kept.len() == DIGEST_KEY_LEN + DIGEST_LEN && kept[..DIGEST_KEY_LEN] == key {
+      let mut digest: [u8; DIGEST_LEN] = [0; DIGEST_LEN];
+      digest.copy_from_slice(&kept[DIGEST_KEY_LEN..]);
+      return Ok(digest);
+    }
+  }
+
+  let mut digest: Sha256 = Sha256::new();
+  io::copy(file, &mut digest)?;
+  let digest: [u8; DIGEST_LEN] = digest.finish();
+  keep_digest(path, &metadata, &digest);
+  Ok(digest)
+}
+
+
+/// Remove the digest kept of the file at the provided path.
+pub fn forget_digest(path: &Path) {
+  if let Ok(p) = digest_path(path) {
+    let _ = fs::remove_file(p);
+  }
+}
+
+
+/// Keep the digest of the file at the provided path with the provided metadata.
+///
+/// The digest is only a cache, so failing to keep it is ignored.
+fn keep_digest(path: &Path, metadata: &Metadata, digest: &[u8; DIGEST_LEN]) {
+  if let Ok(p) = digest_path(path) {
+    let _ = fs::write(p, [&digest_key(metadata)[..], digest].concat());
+  }
+}
+
+
+/// Get the path of the hidden file the digest of the file at the provided path is kept in.
+fn digest_path(path: &Path) -> io::Result<PathBuf> {
+  match path.file_name() {
+    Some(n) => Ok(path.with_file_name(format!(".{}{}", n.to_string_lossy(), DIGEST_SUFFIX))),
+    None => Err(io::Error::new(ErrorKind::InvalidInput, "path does not name a file")),
+  }
+}
+
+
+/// Get the size and modification time of a file, which its kept digest is valid for.
+fn digest_key(metadata: &Metadata) -> [u8; DIGEST_KEY_LEN] {
+  let mtime: u128 = metadata.modified().ok()
+    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+    .map_or(0, |d| d.as_nanos());
+
+  let mut key: [u8; DIGEST_KEY_LEN] = [0; DIGEST_KEY_LEN];
+  key[..8].copy_from_slice(&metadata.len().to_be_bytes());
+  key[8..].copy_from_slice(&mtime.to_be_bytes());
+  key
+}
+
+
+/// Rename the provided temporary file, already flushed to disk, over the
+/// file at the provided path, persisting the rename.
+fn replace(tmp: &Path, path: &Path) -> io::Result<()> {
+  fs::rename(tmp, path)?;
+
+  // not every system can sync a directory
+  if let Some(dir) = path.parent() {
+    let _ = File::open(dir).and_then(|d| d.sync_all());
+  }
+  Ok(())
+}
+
+
+/// Get the start of the names of partial files of uploads to the provided path.
+fn partial_prefix(path: &Path) -> io::Result<String> {
+  match path.file_name() {
+    Some(n) => Ok(format!(".{}.", n.to_string_lossy())),
+    None => Err(io::Error::new(ErrorKind::InvalidInput, "upload path does not name a file")),
+  }
+}
+
+
+/// Find the partial file of the upload with the provided ID to the provided
+/// path, and the size of the whole file.
+fn find(path: &Path, id: u64) -> io::Result<(PathBuf, u64)> {
+  let prefix: String = format!("{}{:016x}.", partial_prefix(path)?, id);
+  let dir: &Path = path.parent().unwrap_or(Path::new("."));
+
+  for e in fs::read_dir(dir)? {
+    let name: String = e?.file_name().to_string_lossy().to_string();
+    let length: Option<u64> = name.strip_prefix(&prefix)
+      .and_then(|n| n.strip_suffix(PARTIAL_SUFFIX))
+      .and_then(|l| l.parse::<u64>().ok());
+
+    if let Some(l) = length {
+      return Ok((dir.join(name), l));
+    }
+  }
+
+  Err(io::Error::new(ErrorKind::NotFound, format!("no upload {:016x} of {}", id, path.display())))
+}
+
+
+/// Let another transfer continue the upload kept in the provided partial file.
+fn release(partial: &Path) {
+  APPENDING.lock().unwrap_or_else(|e| e.into_inner()).retain(|p| p != partial);
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Create an empty directory for a test to store files in.
+  fn scratch(test: &str) -> PathBuf {
+    let dir: PathBuf = std::env::temp_dir().join(format!("storage-{}-{}", test, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+
+  #[test]
+  fn parent_dirs_are_malformed() {
+    for name in ["..", "./..", "./a/../../b", "a/../b"] {
+      assert!(matches!(relative(name), Err(Serr::BAD(_))), "{}", name);
+      assert!(matches!(relative_dir(name), Err(Serr::BAD(_))), "{}", name);
+    }
+  }
+
+
+  #[test]
+  fn absolute_paths_are_malformed() {
+    for name in ["/etc/passwd", "/", "//a"] {
+      assert!(matches!(relative(name), Err(Serr::BAD(_))), "{}", name);
+      assert!(matches!(relative_dir(name), Err(Serr::BAD(_))), "{}", name);
+    }
+  }
+
+
+  #[test]
+  fn relative_paths() {
+    assert_eq!(relative("./a/./b.txt").unwrap(), PathBuf::from("a/b.txt"));
+    assert_eq!(relative("a//b.txt").unwrap(), PathBuf::from("a/b.txt"));
+    assert_eq!(relative_dir("./a/").unwrap(), PathBuf::from("a"));
+  }
+
+
+  #[test]
+  fn root_is_only_a_directory() {
+    let dir: PathBuf = scratch("root");
+    let root: Root = Root::new(dir.to_str().unwrap()).unwrap();
+
+    for name in [".", "./", ""] {
+      assert!(matches!(root.resolve(name), Err(Serr::BAD(_))), "{:?}", name);
+      assert_eq!(root.resolve_dir(name).unwrap(), root.dir, "{:?}", name);
+    }
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+
+  #[cfg(unix)]
+  #[test]
+  fn symlinks_may_not_leave_the_root() {
+    use std::os::unix::fs::symlink;
+
+    let dir: PathBuf = scratch("symlinks");
+    let outside: PathBuf = scratch("symlinks-outside");
+    fs::create_dir(dir.join("inside")).unwrap();
+    fs::write(dir.join("inside/f.txt"), "f").unwrap();
+    fs::write(outside.join("secret.txt"), "s").unwrap();
+    symlink(&outside, dir.join("out")).unwrap();
+    symlink(outside.join("secret.txt"), dir.join("secret.txt")).unwrap();
+    symlink(outside.join("missing.txt"), dir.join("dangling.txt")).unwrap();
+    symlink(dir.join("inside"), dir.join("in")).unwrap();
+    let root: Root = Root::new(dir.to_str().unwrap()).unwrap();
+
+    for name in ["./out/secret.txt", "./out/new.txt", "./secret.txt", "./dangling.txt", "./dangling.txt/x"] {
+      assert!(matches!(root.resolve(name), Err(Serr::FORBIDDEN(_))), "{}", name);
+    }
+    assert!(matches!(root.resolve_dir("./out"), Err(Serr::FORBIDDEN(_))));
+
+    assert_eq!(root.resolve("./in/f.txt").unwrap(), root.dir.join("in/f.txt"));
+    assert_eq!(root.resolve("./in/new/g.txt").unwrap(), root.dir.join("in/new/g.txt"));
+    assert_eq!(root.resolve_dir("./in").unwrap(), root.dir.join("in"));
+    let _ = fs::remove_dir_all(&dir);
+    let _ = fs::remove_dir_all(&outside);
+  }
+
+
+  #[test]
+  fn reserved_names_are_forbidden() {
+    let dir: PathBuf = scratch("reserved");
+    let root: Root = Root::new(dir.to_str().unwrap()).unwrap();
+
+    for name in ["./.a.digest", "./sub/.a.txt.1.0.part", "./.a.txt.00ff.10.upload"] {
+      assert!(matches!(root.resolve(name), Err(Serr::FORBIDDEN(_))), "{}", name);
+    }
+    for name in ["./a.digest", "./.a", "./.part"] {
+      assert!(root.resolve(name).is_ok(), "{}", name);
+    }
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+
+  #[test]
+  fn aborted_resume_is_cut_back() {
+    let dir: PathBuf = scratch("abort");
+    let path: PathBuf = dir.join("f.bin");
+    let id: u64 = Resumable::create(&path, 10).unwrap();
+
+    let mut upload: Resumable = Resumable::resume(&path, id, 0, 4).unwrap();
+    upload.write_all(b"abcd").unwrap();
+    upload.commit().unwrap();
+    drop(upload);
+
+    let mut upload: Resumable = Resumable::resume(&path, id, 4, 6).unwrap();
+    upload.write_all(b"efghij").unwrap();
+    upload.abort();
+    upload.commit().unwrap();
+    drop(upload);
+
+    assert_eq!(Resumable::status(&path, id).unwrap(), (4, 10));
+    assert!(!path.exists());
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
@@ -0,0 +1,494 @@
+use std::{io::{BufRead, Read, Write}, net::TcpStream};
+
+use protocol::{LF, CR, Serr};
+
+/// Longest line of a request, and longest value of a field once unfolded
+const MAX_LINE: usize = 8192;
+
+/// Most fields in the header of a request
+const MAX_FIELDS: usize = 100;
+
+/// Most lines a field may be folded across
+const MAX_FOLDS: usize = 8;
+
+/// Most empty lines read before a request line
+const MAX_EMPTY_LINES: usize = 8;
+
+/// Interim response asking a client that sent Expect: 100-continue for the body
+const CONTINUE_100: &[u8] = "HTTP/1.1 100 CONTINUE\r\n\r\n".as_bytes();
+
+
+/// An enumeration of the HTTP methods of requests.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum Method {
+  GET,
+  POST,
+  HEAD,
+  DELETE,
+  PATCH,
+  OTHER(String),
+}
+
+
+/// A client's connection, whether it's kept open for another request
+/// after the response, and whether it waits to be asked for the body.
+#[derive(Clone, Copy)]
+pub struct Client<'a> {
+  pub stream: &'a TcpStream,
+  pub keep_alive: bool,
+  pub expect_continue: bool,
+}
+
+
+impl Client<'_> {
+  /// Ask the client for the body of its request, if it waits to be asked,
+  /// once the request was accepted.
+  pub fn proceed(&self) {
+    let mut stream: &TcpStream = self.stream;
+    if self.expect_continue {
+      let _ = stream.write_all(CONTINUE_100);
+    }
+  }
+}
+
+
+/// The request line and header of an HTTP/1.x request.
+///
+/// The body, if any, is left in the reader.
+#[derive(Debug)]
+pub struct Request {
+  pub method: Method,
+  pub path: String,  // percent-decoded, without the query
+  pub query: Option<String>,  // as sent, after the ?
+  pub minor: u8,  // minor version of HTTP/1.x
+  fields: Vec<(String, String)>,  // names in lowercase, values trimmed
+}
+
+
+impl Request {
+  /// Parse the request line and header of the next request read.
+  ///
+  /// Returns None if the reader ended or timed out before a request started,
+  /// and BAD if the request is malformed or exceeds the limits.
+  pub fn parse<R: BufRead>(reader: &mut R) -> Result<Option<Request>, Serr> {
+    let mut line: Option<String> = None;
+
+    // a client may send empty lines between requests
+    for _ in 0..MAX_EMPTY_LINES {
+      match read_line(reader)? {
+        None => return Ok(None),
+        Some(l) if l.is_empty() => continue,
+        Some(l) => { line = Some(l); break; },
+      }
+    }
+    let line: String = match line {
+      Some(l) => l,
+      None => return Err(Serr::BAD("Request started with too many empty lines".to_string())),
+    };
+
+    let mut request: Request = Request::parse_request_line(&line)?;
+    request.parse_fields(reader)?;
+    request.validate()?;
+    Ok(Some(request))
+  }
+
+
+  /// Parse the method, path and version of a request line,
+  /// e.g. GET /path/to%20file?query HTTP/1.1
+  fn parse_request_line(line: &str) -> Result<Request, Serr> {
+    // paths with unescaped spaces are tolerated, as the version can't hold any
+    let (method, target, version) = match line.split_once(' ').and_then(|(m, rest)| rest.rsplit_once(' ').map(|(t, v)| (m, t, v))) {
+      Some((m, t, v)) if !m.is_empty() && !t.is_empty() => (m, t, v),
+      _ => return Err(Serr::BAD(format!("Malformed request line {:?}", line))),
+    };
+
+    if !method.bytes().all(is_token) {
+      return Err(Serr::BAD(format!("Malformed method {:?}", method)));
+    }
+    let minor: u8 = match version {
+      "HTTP/1.1" => 1,
+      "HTTP/1.0" => 0,
+      _ => return Err(Serr::BAD(format!("Unsupported HTTP version {:?}", version))),
+    };
+
+    // a target may be absolute, naming the proxy itself
+    let target: &str = match target.split_once("://") {
+      Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => match rest.find('/') {
+        Some(i) => &rest[i..],
+        None => "/",
+      },
+      _ => target,
+    };
+    if !target.starts_with('/') {
+      return Err(Serr::BAD(format!("Request target {:?} is not a path", target)));
+    }
+
+    let (path, query) = match target.split_once('?') {
+      Some((p, q)) => (p, Some(q.to_string())),
+      None => (target, None),
+    };
+
+    Ok(Request {
+      method: match method {
+        "GET" => Method::GET,
+        "POST" => Method::POST,
+        "HEAD" => Method::HEAD,
+        "DELETE" => Method::DELETE,
+        "PATCH" => Method::PATCH,
+        m => Method::OTHER(m.to_string()),
+      },
+      path: percent_decode(path)?,
+      query,
+      minor,
+      fields: Vec::new(),
+    })
+  }
+
+
+  /// Parse the fields of the header, up to the empty line ending it.
+  ///
+  /// Fields folded across lines are joined by a space.
+  fn parse_fields<R: BufRead>(&mut self, reader: &mut R) -> Result<(), Serr> {
+    let mut folds: usize = 0;
+
+    loop {
+      let line: String = match read_line(reader)? {
+        Some(l) => l,
+        None => return Err(Serr::BAD("Request ended before its header did".to_string())),
+      };
+      if line.is_empty() { return Ok(()); }
+
+      // a line starting with whitespace continues the previous field
+      if line.starts_with([' ', '\t']) {
+        let value: &mut String = match self.fields.last_mut() {
+          Some((_, v)) => v,
+          None => return Err(Serr::BAD("Header started with a folded line".to_string())),
+        };
+        folds += 1;
+        if folds >= MAX_FOLDS || value.len() + line.len() > MAX_LINE {
+          return Err(Serr::BAD("Header field folded across too many lines".to_string()));
+        }
+        value.push(' ');
+        value.push_str(line.trim());
+        continue;
+      }
+
+      let (name, value) = match line.split_once(':') {
+        Some((n, v)) if !n.is_empty() && n.bytes().all(is_token) => (n.to_ascii_lowercase(), v.trim().to_string()),
+        _ => return Err(Serr::BAD(format!("Malformed header field {:?}", line))),
+      };
+      if self.fields.len() >= MAX_FIELDS {
+        return Err(Serr::BAD(format!("Header has more than {} fields", MAX_FIELDS)));
+      }
+      self.fields.push((name, value));
+      folds = 0;
+    }
+  }
+
+
+  /// Ensure the fields describe a request that can be served.
+  fn validate(&self) -> Result<(), Serr> {
+    if self.minor == 1 && self.field("host").is_none() {
+      return Err(Serr::BAD("HTTP/1.1 request has no Host field".to_string()));
+    }
+
+    // a body is only delimited by its length
+    if self.field("transfer-encoding").is_some() {
+      return Err(Serr::NA);
+    }
+    self.content_length()?;
+    Ok(())
+  }
+
+
+  /// Determine if the client asks for the connection to be kept open after
+  /// the response, which HTTP/1.1 does unless it sends Connection: close,
+  /// and HTTP/1.0 doesn't unless it sends Connection: keep-alive.
+  pub fn keep_alive(&self) -> bool {
+    let options: String = self.field("connection").unwrap_or_default().to_ascii_lowercase();
+    let has = |option: &str| options.split(',').any(|o| o.trim() == option);
+
+    match self.minor {
+      0 => has("keep-alive"),
+      _ => !has("close"),
+    }
+  }
+
+
+  /// Determine if the client waits for a 100 Continue before sending the
+  /// body, which only HTTP/1.1 clients may ask for.
+  pub fn expect_continue(&self) -> bool {
+    self.minor == 1 && self.field("expect").is_some_and(|e| e.eq_ignore_ascii_case("100-continue"))
+  }
+
+
+  /// Get the value of the field with the provided name, regardless of case.
+  ///
+  /// Fields sent more than once are joined by commas.
+  pub fn field(&self, name: &str) -> Option<String> {
+    let values: Vec<&str> = self.fields.iter()
+      .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+      .map(|(_, v)| v.as_str())
+      .collect();
+
+    match values.is_empty() {
+      true => None,
+      false => Some(values.join(", ")),
+    }
+  }
+
+
+  /// Get the length of the body, being 0 without a Content-Length field.
+  pub fn content_length(&self) -> Result<u64, Serr> {
+    let value: String = match self.field("content-length") {
+      Some(v) => v,
+      None => return Ok(0),
+    };
+
+    // the field may be repeated, but only with the same length
+    let mut lengths = value.split(',').map(|l| l.trim());
+    let first: &str = lengths.next().unwrap_or_default();
+    if !first.bytes().all(|b| b.is_ascii_digit()) || lengths.any(|l| l != first) {
+      return Err(Serr::BAD(format!("Invalid Content-Length {:?}", value)));
+    }
+
+    match first.parse::<u64>() {
+      Ok(l) => Ok(l),
+      Err(_) => Err(Serr::BAD(format!("Invalid Content-Length {:?}", value))),
+    }
+  }
+
+
+  /// Get the value of the parameter of the query with the provided name,
+  /// e.g. id of ?id=0a, percent-decoded.
+  pub fn query_param(&self, name: &str) -> Result<Option<String>, Serr> {
+    for param in self.query.as_deref().unwrap_or_default().split('&') {
+      if let Some((n, v)) = param.split_once('=') {
+        if n == name { return percent_decode(v).map(Some); }
+      }
+    }
+    Ok(None)
+  }
+}
+
+
+/// Read a line ending in a line feed, without it or a preceding
+/// carriage return.
+///
+/// Returns None if the reader ended or failed before the line started.
+fn read_line<R: BufRead>(reader: &mut R) -> Result<Option<String>, Serr> {
+  let mut buf: Vec<u8> = Vec::new();
+
+  // read no further than the longest line, so a client can't exhaust memory
+  let amt: usize = match reader.by_ref().take(MAX_LINE as u64 + 1).read_until(LF, &mut buf) {
+    Ok(i) => i,
+    Err(_) if buf.is_empty() => return Ok(None),  // timed out waiting, or reset
+    Err(e) => return Err(Serr::BAD(format!("Unable to read request:\n{}", e))),
+  };
+
+  if amt == 0 { return Ok(None); }
+  if buf.last() != Some(&LF) {
+    return match amt > MAX_LINE {
+      true => Err(Serr::BAD(format!("Request line exceeds {} bytes", MAX_LINE))),
+      false => Err(Serr::BAD("Request ended mid-line".to_string())),
+    };
+  }
+
+  buf.pop();
+  if buf.last() == Some(&CR) { buf.pop(); }
+  match String::from_utf8(buf) {
+    Ok(l) => Ok(Some(l)),
+    Err(_) => Err(Serr::BAD("Request line is not valid UTF-8".to_string())),
+  }
+}
+
+
+/// Decode the %XX escapes of a path or query parameter.
+///
+/// Paths decoding to invalid UTF-8 or holding control characters, such as
+/// the CR ending a filename sent to the datastore, are malformed.
+fn percent_decode(s: &str) -> Result<String, Serr> {
+  let bytes: &[u8] = s.as_bytes();
+  let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+  let mut i: usize = 0;
+
+  while i < bytes.len() {
+    if bytes[i] != b'%' {
+      decoded.push(bytes[i]);
+      i += 1;
+      continue;
+    }
+
+    let byte: Option<u8> = bytes.get(i + 1..i + 3)
+      .and_then(|h| std::str::from_utf8(h).ok())
+      .filter(|h| h.bytes().all(|b| b.is_ascii_hexdigit()))
+      .and_then(|h| u8::from_str_radix(h, 16).ok());
+    match byte {
+      Some(b) => decoded.push(b),
+      None => return Err(Serr::BAD(format!("Invalid percent-encoding in {:?}", s))),
+    }
+    i += 3;
+  }
+
+  match String::from_utf8(decoded) {
+    Ok(d) if !d.contains(|c: char| c.is_ascii_control()) => Ok(d),
+    _ => Err(Serr::BAD(format!("{:?} does not decode to a valid path", s))),
+  }
+}
+
+
+/// Encode the bytes of a path that may not appear in a URL as %XX escapes,
+/// undone by percent_decode.
+pub fn percent_encode(s: &str) -> String {
+  let mut encoded: String = String::with_capacity(s.len());
+
+  for b in s.bytes() {
+    match b.is_ascii_alphanumeric() || b"/-._~".contains(&b) {
+      true => encoded.push(b as char),
+      false => encoded.push_str(&format!("%{:02X}", b)),
+    }
+  }
+
+  encoded
+}
+
+
+/// Determine if a byte may be part of a method or field name.
+fn is_token(b: u8) -> bool {
+  b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  fn parse(request: &str) -> Result<Option<Request>, Serr> {
+    Request::parse(&mut request.as_bytes())
+  }
+
+
+  fn folded(lines: usize) -> String {
+    format!("GET / HTTP/1.1\r\nHost: a\r\nX-Long: a\r\n{}\r\n", " b\r\n".repeat(lines))
+  }
+
+
+  #[test]
+  fn request_line_and_fields() {
+    let request: Request = parse("\r\n\r\nGET http://proxy/dir/a%20b.txt?upload=0a HTTP/1.1\r\nHost: proxy\r\nX-A: 1\r\nx-a:  2 \r\n\r\nbody").unwrap().unwrap();
+    assert_eq!(request.method, Method::GET);
+    assert_eq!(request.path, "/dir/a b.txt");
+    assert_eq!(request.query.as_deref(), Some("upload=0a"));
+    assert_eq!(request.query_param("upload").unwrap().as_deref(), Some("0a"));
+    assert_eq!(request.field("x-a").as_deref(), Some("1, 2"));
+    assert_eq!(request.content_length().unwrap(), 0);
+  }
+
+
+  #[test]
+  fn empty_reader_has_no_request() {
+    assert!(parse("").unwrap().is_none());
+    assert!(parse("\r\n\n").unwrap().is_none());
+    assert!(matches!(parse(&"\r\n".repeat(MAX_EMPTY_LINES + 1)), Err(Serr::BAD(_))));
+  }
+
+
+  #[test]
+  fn malformed_requests() {
+    for request in [
+      "GET /\r\n\r\n",
+      "GET / HTTP/2.0\r\n\r\n",
+      "GET a.txt HTTP/1.0\r\n\r\n",
+      "G(T / HTTP/1.0\r\n\r\n",
+      "GET / HTTP/1.1\r\n\r\n",
+      "GET / HTTP/1.0\r\nNo colon\r\n\r\n",
+      "GET / HTTP/1.0\r\n folded: first\r\n\r\n",
+      "GET / HTTP/1.0\r\nHost: a\r\n",
+    ] {
+      assert!(matches!(parse(request), Err(Serr::BAD(_))), "{:?}", request);
+    }
+  }
+
+
+  #[test]
+  fn long_lines() {
+    let path: String = "a".repeat(MAX_LINE);
+    assert!(matches!(parse(&format!("GET /{} HTTP/1.0\r\n\r\n", path)), Err(Serr::BAD(_))));
+    assert!(parse(&format!("GET /{} HTTP/1.0\r\n\r\n", &path[..100])).is_ok());
+  }
+
+
+  #[test]
+  fn folding_limits() {
+    let request: Request = parse(&folded(MAX_FOLDS - 1)).unwrap().unwrap();
+    assert_eq!(request.field("x-long").unwrap(), format!("a{}", " b".repeat(MAX_FOLDS - 1)));
+    assert!(matches!(parse(&folded(MAX_FOLDS)), Err(Serr::BAD(_))));
+
+    let long: String = format!("GET / HTTP/1.0\r\nX-Long: {}\r\n {}\r\n\r\n", "a".repeat(MAX_LINE / 2), "b".repeat(MAX_LINE / 2));
+    assert!(matches!(parse(&long), Err(Serr::BAD(_))));
+  }
+
+
+  #[test]
+  fn too_many_fields() {
+    let fields: String = "X-A: 1\r\n".repeat(MAX_FIELDS + 1);
+    assert!(matches!(parse(&format!("GET / HTTP/1.0\r\n{}\r\n", fields)), Err(Serr::BAD(_))));
+  }
+
+
+  #[test]
+  fn duplicate_content_length() {
+    let request: Request = parse("POST /a HTTP/1.0\r\nContent-Length: 5\r\nContent-Length: 5\r\n\r\n").unwrap().unwrap();
+    assert_eq!(request.content_length().unwrap(), 5);
+
+    for lengths in ["5\r\nContent-Length: 6", "5, 6", "-5", "+5", "99999999999999999999"] {
+      let request: String = format!("POST /a HTTP/1.0\r\nContent-Length: {}\r\n\r\n", lengths);
+      assert!(matches!(parse(&request), Err(Serr::BAD(_))), "{:?}", lengths);
+    }
+  }
+
+
+  #[test]
+  fn transfer_encoding_is_not_implemented() {
+    assert!(matches!(parse("POST /a HTTP/1.0\r\nTransfer-Encoding: chunked\r\n\r\n"), Err(Serr::NA)));
+  }
+
+
+  #[test]
+  fn percent_decoding_errors() {
+    assert_eq!(percent_decode("/caf%C3%A9%2Fx").unwrap(), "/café/x");
+    for path in ["/%zz", "/%4", "/%", "/%C3", "/%00", "/a%0D%0Ab", "/%7F"] {
+      assert!(matches!(percent_decode(path), Err(Serr::BAD(_))), "{:?}", path);
+    }
+    assert!(matches!(parse("GET /%0Dx HTTP/1.0\r\n\r\n"), Err(Serr::BAD(_))));
+  }
+
+
+  #[test]
+  fn percent_encoding_round_trips() {
+    for path in ["/a b/c?d#e.txt", "/café", "/100%"] {
+      assert_eq!(percent_decode(&percent_encode(path)).unwrap(), path);
+    }
+  }
+
+
+  #[test]
+  fn keep_alive() {
+    let keep_alive = |request: &str| parse(request).unwrap().unwrap().keep_alive();
+    assert!(keep_alive("GET / HTTP/1.1\r\nHost: a\r\n\r\n"));
+    assert!(!keep_alive("GET / HTTP/1.1\r\nHost: a\r\nConnection: Upgrade, Close\r\n\r\n"));
+    assert!(!keep_alive("GET / HTTP/1.0\r\n\r\n"));
+    assert!(keep_alive("GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n"));
+  }
+
+
+  #[test]
+  fn expect_continue() {
+    let expect_continue = |request: &str| parse(request).unwrap().unwrap().expect_continue();
+    assert!(expect_continue("POST /a HTTP/1.1\r\nHost: a\r\nExpect: 100-Continue\r\nContent-Length: 5\r\n\r\n"));
+    assert!(!expect_continue("POST /a HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\n"));
+    assert!(!expect_continue("POST /a HTTP/1.0\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n"));
+  }
+}
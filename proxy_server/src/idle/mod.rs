@@ -0,0 +1,108 @@
+use std::{net::TcpStream, io::ErrorKind, sync::{Arc, atomic::{AtomicUsize, Ordering}, mpsc::{Receiver, Sender, channel}}, thread, time::{Duration, Instant}};
+
+/// Longest a worker waits for the next request before
+/// checking if other clients wait for a worker
+const SLICE: Duration = Duration::from_millis(50);
+
+/// Time between checks of the connections waiting for their next request
+const POLL: Duration = Duration::from_millis(5);
+
+
+/// An enumeration of the outcomes of waiting for the next request of a connection.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum Wait {
+  READY,  // the request started
+  PARKED,  // handed off to wait without a worker
+  CLOSED,  // closed by the client, or idle for too long
+}
+
+
+/// Connections kept open between requests, waiting for the next one.
+///
+/// A worker waits for the next request of its connection for as long
+/// as no other client waits for a worker. Otherwise the connection is
+/// parked on a thread watching idle connections, which hands it back
+/// to the pool once its next request starts.
+#[derive(Clone)]
+pub struct Idle {
+  parked: Sender<(TcpStream, Instant)>,  // connections, and when they're closed
+  pending: Arc<AtomicUsize>,  // jobs waiting for a worker
+}
+
+
+impl Idle {
+  /// Watch parked connections, resuming each one whose next request
+  /// started with the provided function, which may park it again.
+  ///
+  /// Workers hand their connection off once pending counts jobs waiting for a worker.
+  pub fn new<F: Fn(TcpStream, Idle) + Send + 'static>(pending: Arc<AtomicUsize>, resume: F) -> Idle {
+    let (tx, rx) = channel::<(TcpStream, Instant)>();
+    let idle: Idle = Idle { parked: tx, pending };
+
+    let watcher: Idle = idle.clone();
+    thread::spawn(move || watch(rx, watcher, resume));
+    idle
+  }
+
+
+  /// Wait for the next request of the provided connection,
+  /// no longer than the provided time.
+  pub fn wait(&self, stream: &TcpStream, timeout: Duration) -> Wait {
+    let deadline: Instant = Instant::now() + timeout;
+
+    loop {
+      let left: Duration = deadline.saturating_duration_since(Instant::now());
+      if left.is_zero() { return Wait::CLOSED; }
+
+      // let another client have the worker meanwhile
+      if self.pending.load(Ordering::Relaxed) > 0 {
+        return match stream.try_clone().map(|s| self.parked.send((s, deadline))) {
+          Ok(Ok(_)) => Wait::PARKED,
+          _ => Wait::CLOSED,
+        };
+      }
+
+      let _ = stream.set_read_timeout(Some(left.min(SLICE)));
+      match stream.peek(&mut [0; 1]) {
+        Ok(0) => return Wait::CLOSED,
+        Ok(_) => return Wait::READY,
+        Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => continue,
+        Err(_) => return Wait::CLOSED,
+      }
+    }
+  }
+}
+
+
+/// Check the parked connections, resuming those whose next request
+/// started and closing those that were closed or idle for too long.
+fn watch<F: Fn(TcpStream, Idle)>(rx: Receiver<(TcpStream, Instant)>, idle: Idle, resume: F) {
+  let mut parked: Vec<(TcpStream, Instant)> = Vec::new();
+
+  loop {
+    // sleep until a connection is parked while none is
+    if parked.is_empty() {
+      match rx.recv() {
+        Ok(c) => parked.push(c),
+        Err(_) => return,
+      }
+    }
+    parked.extend(rx.try_iter());
+
+    for (stream, deadline) in std::mem::take(&mut parked) {
+      let _ = stream.set_nonblocking(true);
+      match stream.peek(&mut [0; 1]) {
+        Ok(0) => (),
+        Ok(_) => {
+          let _ = stream.set_nonblocking(false);
+          resume(stream, idle.clone());
+        },
+        Err(e) if e.kind() == ErrorKind::WouldBlock && Instant::now() < deadline => parked.push((stream, deadline)),
+        Err(_) => (),
+      }
+    }
+
+    thread::sleep(POLL);
+  }
+}
@@ -0,0 +1,112 @@
+use protocol::listing::Entry;
+
+use crate::{server_handle::http_date, http::percent_encode};
+
+/// Media type of a listing rendered as JSON
+pub const JSON: &str = "application/json";
+
+/// Media type of a listing rendered as HTML
+pub const HTML: &str = "text/html";
+
+
+/// Determine if the client prefers a listing as HTML over JSON,
+/// by the quality values of its Accept field.
+///
+/// JSON is preferred when both are as acceptable, or no Accept field was sent.
+pub fn prefers_html(accept: Option<&str>) -> bool {
+  let mut html: (u8, f32) = (0, 0.0);  // specificity and quality of the range matching HTML
+  let mut json: (u8, f32) = (0, 0.0);
+
+  for range in accept.unwrap_or_default().split(',') {
+    let mut params = range.split(';').map(|p| p.trim());
+    let media: String = params.next().unwrap_or_default().to_ascii_lowercase();
+    let q: f32 = params
+      .find_map(|p| p.strip_prefix("q="))
+      .and_then(|q| q.parse::<f32>().ok())
+      .unwrap_or(1.0);
+
+    // the most specific range matching a type sets its quality
+    let (html_match, json_match): (u8, u8) = match media.as_str() {
+      HTML => (3, 0),
+      JSON => (0, 3),
+      "text/*" => (2, 0),
+      "application/*" => (0, 2),
+      "*/*" => (1, 1),
+      _ => (0, 0),
+    };
+    if html_match > html.0 { html = (html_match, q); }
+    if json_match > json.0 { json = (json_match, q); }
+  }
+
+  html.1 > json.1
+}
+
+
+/// Render the entries of the directory at the provided path as a JSON object, e.g.
+/// {"path":"/docs/","entries":[{"name":"a.txt","size":6,"mtime":0,"directory":false}]}
+pub fn json(path: &str, entries: &[Entry]) -> String {
+  let entries: Vec<String> = entries.iter().map(|e| format!(
+    "{{\"name\":\"{}\",\"size\":{},\"mtime\":{},\"directory\":{}}}",
+    json_escape(&e.name), e.size, e.mtime, e.dir
+  )).collect();
+
+  format!("{{\"path\":\"{}\",\"entries\":[{}]}}", json_escape(path), entries.join(","))
+}
+
+
+/// Render the entries of the directory at the provided path as an HTML page,
+/// linking to each entry.
+pub fn html(path: &str, entries: &[Entry]) -> String {
+  let path: String = html_escape(path);
+  let mut rows: String = String::new();
+
+  for e in entries {
+    let name: String = html_escape(&e.name);
+    let href: String = percent_encode(&e.name);
+    let slash: &str = if e.dir { "/" } else { "" };
+    let size: String = if e.dir { "-".to_string() } else { e.size.to_string() };
+    rows.push_str(&format!("<tr><td><a href=\"./{0}{1}\">{2}{1}</a></td><td>{3}</td><td>{4}</td></tr>\n", href, slash, name, size, http_date(e.mtime)));
+  }
+
+  format!("<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {0}</title></head>\n<body>\n<h1>Index of {0}</h1>\n\
+    <table>\n<tr><th>Name</th><th>Size</th><th>Modified</th></tr>\n{1}</table>\n</body>\n</html>\n", path, rows)
+}
+
+
+/// Escape a string to be placed between the quotes of a JSON string.
+fn json_escape(s: &str) -> String {
+  let mut escaped: String = String::with_capacity(s.len());
+
+  for c in s.chars() {
+    match c {
+      '"' => escaped.push_str("\\\""),
+      '\\' => escaped.push_str("\\\\"),
+      '\n' => escaped.push_str("\\n"),
+      '\r' => escaped.push_str("\\r"),
+      '\t' => escaped.push_str("\\t"),
+      c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+      c => escaped.push(c),
+    }
+  }
+
+  escaped
+}
+
+
+/// Escape a string to be placed in HTML text or a quoted attribute.
+fn html_escape(s: &str) -> String {
+  let mut escaped: String = String::with_capacity(s.len());
+
+  for c in s.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&#39;"),
+      c => escaped.push(c),
+    }
+  }
+
+  escaped
+}
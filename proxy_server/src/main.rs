@@ -1,30 +1,42 @@
+#![allow(clippy::upper_case_acronyms, clippy::needless_late_init)]
+
 pub mod server_handle;
-pub mod protocol;
+pub mod pool;
+pub mod listing;
+pub mod http;
+pub mod idle;
 
-use std::{net::{TcpListener, UdpSocket, TcpStream}, io::{Write, BufReader, BufRead, Read, copy}, fs::File};
+use std::{net::{TcpListener, UdpSocket, TcpStream, SocketAddr, IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs}, io::{Write, BufReader}, sync::Arc, thread, time::Duration};
 
-use protocol::{SLEEP_TIME, LF, CRLF};
+use protocol::{CRLF, DATASTORE_PORT, Serr, Span, connection::Limits, config::{Config, LIMIT_KEYS}, storage::{relative, relative_dir}, log, info, warn, error};
+use server_handle::Body;
+use http::{Client, Request, Method};
+use pool::ThreadPool;
+use idle::{Idle, Wait};
 
-/// Length of HTTP version
-const HTTP_LEN: usize = 8;
+/// Usage of the proxy
+const USAGE: &str = "usage: proxy_server [<datastore server address> [worker count]] [--config <file>]
+                    [--datastore <address>] [--workers <count>] [--bind <address>] [--port <port>] [--keepalive <seconds>]
+                    [--retries <count>] [--deadline <seconds>] [--timeout <milliseconds>]
+                    [--window <packets>] [--mtu <bytes>] [--log <off|error|warn|info|debug>]";
 
-/// ASCII values for HTTP/1.1
-const HTTP11: [u8; HTTP_LEN] = [72, 84, 84, 80, 47, 49, 46, 49];
+/// Keys configuring the proxy, besides the limits of transfers
+const KEYS: [&str; 5] = ["datastore", "workers", "bind", "port", "keepalive"];
 
-///ASCII values for HTTP/1.0
-const HTTP10: [u8; HTTP_LEN] = [72, 84, 84, 80, 47, 49, 46, 48];
+/// Port the proxy listens on for clients
+const PORT: u16 = 40000;
 
-/// Length of GET
-const LEN_GET: usize = 3;
+/// Number of workers serving clients when none is provided
+const DEFAULT_WORKERS: usize = 4;
 
-/// ASCII values for GET request
-const GET: [u8; LEN_GET] = [71, 69, 84];
+/// Seconds a connection is kept open waiting for another request when none is provided
+const DEFAULT_KEEPALIVE: u64 = 5;
 
-/// Length of POST
-const LEN_POST: usize = 4;
+/// Connection field of a response keeping the connection open
+const KEEP_ALIVE: &[u8] = "Connection: keep-alive\r\n".as_bytes();
 
-/// ASCII values for POST request
-const POST: [u8; LEN_POST] = [80, 79, 83, 84];
+/// Connection field of a response closing the connection
+const CLOSE: &[u8] = "Connection: close\r\n".as_bytes();
 
 /// Length of the mime type field Content-Length
 const CLEN_LEN: usize = 16;
@@ -32,36 +44,11 @@ const CLEN_LEN: usize = 16;
 /// ASCII values for Content-Length: 
 const CLEN: [u8; CLEN_LEN] = [67, 111, 110, 116, 101, 110, 116, 45, 76, 101, 110, 103, 116, 104, 58, 32];
 
-/// Minimum Ethernet MTU in bytes
-const ETHER_MTU: usize = 1500;
-
-/// UDP header length in bytes
-const UDP_H_LEN: usize = 8;
-
-/// IP header length in bytes
-const IP_H_LEN: usize = 20;
-
-/// Minimum MTU in bytes
-const MTU: usize = ETHER_MTU - UDP_H_LEN - IP_H_LEN;
-
-
-/// Enum of the possible errors.
-#[derive(Debug)]
-#[derive(PartialEq)]
-pub enum Serr {
-  DNE(String),
-  SERVER(String),
-  NA,
-}
-
+/// Unit of the ranges of a Range field
+const BYTES_UNIT: &str = "bytes=";
 
-/// Enum of the possible errors.
-#[derive(Debug)]
-#[derive(PartialEq)]
-pub enum ReadData {
-  MORE,
-  DONE,
-}
+/// Parameter of the query of a path naming a resumable upload of the file, by its ID in hex
+const UPLOAD_PARAM: &str = "upload";
 
 
 /// An enumeration of supported HTTP operations.
@@ -70,252 +57,529 @@ pub enum ReadData {
 enum Op {
   GET(String),
   POST(String),
+  DELETE(String),
+  HEAD(String),
+  LIST(String),
+  PATCH(String),
   NA,
 }
 
 
+/// Error 400 response
+const ERROR_400: &[u8] = "HTTP/1.1 400 BAD REQUEST\r\nContent-Length: 0\r\n\r\n".as_bytes();
+
+/// Error 403 response
+const ERROR_403: &[u8] = "HTTP/1.1 403 FORBIDDEN\r\nContent-Length: 0\r\n\r\n".as_bytes();
+
 /// Error 404 response
-const ERROR_404: &[u8] = "HTTP/1.1 404 NOT FOUND\r\n\r\n".as_bytes();
+const ERROR_404: &[u8] = "HTTP/1.1 404 NOT FOUND\r\nContent-Length: 0\r\n\r\n".as_bytes();
+
+/// Error 409 response
+const ERROR_409: &[u8] = "HTTP/1.1 409 CONFLICT\r\nContent-Length: 0\r\n\r\n".as_bytes();
+
+/// Error 416 response
+const ERROR_416: &[u8] = "HTTP/1.1 416 RANGE NOT SATISFIABLE\r\nContent-Length: 0\r\n\r\n".as_bytes();
 
 /// Error 500 response
-const ERROR_500: &[u8] = "HTTP/1.1 500 INTERNAL SERVER ERROR\r\n\r\n".as_bytes();
+const ERROR_500: &[u8] = "HTTP/1.1 500 INTERNAL SERVER ERROR\r\nContent-Length: 0\r\n\r\n".as_bytes();
+
+/// Error 501 response
+const ERROR_501: &[u8] = "HTTP/1.1 501 NOT IMPLEMENTED\r\nContent-Length: 0\r\n\r\n".as_bytes();
+
+/// Error 504 response
+const ERROR_504: &[u8] = "HTTP/1.1 504 GATEWAY TIMEOUT\r\nContent-Length: 0\r\n\r\n".as_bytes();
 
 
 fn main() {
-  let args:Vec<String> = std::env::args().collect();
-  let ds_addr: String;  // get datastore IP address as command line arg
-  let addr: String = "0.0.0.0:40000".to_string();  // listen on all addresses
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let config: Config;
+  let ds_addr: SocketAddr;  // get datastore address as command line arg
+  let workers: usize;
+  let limits: Limits;
+  let port: u16;
+  let keep_alive: Option<Duration>;
+
+  if args.iter().any(|a| a == "-h" || a == "--help") {
+    println!("{}", USAGE);
+    return;
+  }
 
-  if args.len() != 2 {
-    panic!("usage: proxy_server <datastore server IP>");
+  config = match Config::from_args("proxy", &[&KEYS[..], &LIMIT_KEYS[..]].concat(), &args) {
+    Ok(c) => c,
+    Err(e) => panic!("{}\n{}", e, USAGE),
+  };
+  if config.positional.len() > 2 {
+    panic!("unexpected argument {}\n{}", config.positional[2], USAGE);
   }
-  ds_addr = format!("{}:41000", &args[1]);
 
-  let l: TcpListener = match TcpListener::bind(&addr) {
-    Ok(tl) => tl,
+  // the datastore and worker count may be given before any flags
+  ds_addr = match config.positional.first().map(|d| d.as_str()).or(config.get("datastore")) {
+    Some(d) => match resolve(d) {
+      Ok(a) => a,
+      Err(e) => panic!("{}", e),
+    },
+    None => panic!("no datastore server address provided\n{}", USAGE),
+  };
+  workers = match config.positional.get(1) {
+    Some(w) => w.parse::<usize>().ok(),
+    None => config.parse_or("workers", DEFAULT_WORKERS).ok(),
+  }.filter(|w| *w > 0).unwrap_or_else(|| panic!("worker count must be a positive integer"));
+  match config.log_level() {
+    Ok(l) => log::set_level(l),
+    Err(e) => panic!("{}", e),
+  };
+  limits = match config.limits() {
+    Ok(l) => l,
+    Err(e) => panic!("{}", e),
+  };
+  port = match config.parse_or("port", PORT) {
+    Ok(p) => p,
+    Err(e) => panic!("{}", e),
+  };
+  keep_alive = match config.parse_or("keepalive", DEFAULT_KEEPALIVE) {
+    Ok(0) => None,  // a request per connection
+    Ok(k) => Some(Duration::from_secs(k)),
+    Err(e) => panic!("{}", e),
+  };
+
+  // listen on all addresses, over IPv6 and IPv4
+  let listeners = match config.parse::<IpAddr>("bind") {
+    Ok(Some(ip)) => TcpListener::bind((ip, port)).map(|l| vec![l]),
+    Ok(None) => bind(port),
+    Err(e) => panic!("{}", e),
+  };
+  let mut listeners: Vec<TcpListener> = match listeners {
+    Ok(l) => l,
     Err(_) => {
-      eprintln!("Unable to bind a TCP socket to address");
+      error!("Unable to bind a TCP socket to address");
       return;
     }
   };
+  let pool: Arc<ThreadPool> = Arc::new(ThreadPool::new(workers));
+
+  // connections waiting for their next request are resumed by the pool
+  let resumer: Arc<ThreadPool> = Arc::clone(&pool);
+  let idle: Idle = Idle::new(pool.pending(), move |stream, idle| {
+    resumer.execute(move || handle_client(stream, ds_addr, limits, keep_alive, &idle));
+  });
+
+  // each listener accepts on its own thread, the last on this one
+  let last: Option<TcpListener> = listeners.pop();
+  for l in listeners {
+    let (pool, idle) = (Arc::clone(&pool), idle.clone());
+    thread::spawn(move || accept(l, &pool, &idle, ds_addr, limits, keep_alive));
+  }
+  if let Some(l) = last {
+    accept(l, &pool, &idle, ds_addr, limits, keep_alive);
+  }
+}
+
+
+/// Bind the proxy's listeners to all addresses.
+///
+/// Whether the IPv6 wildcard also accepts IPv4 connections as mapped
+/// addresses depends on the system, so the IPv4 wildcard is bound as
+/// well unless the IPv6 listener already holds it.
+fn bind(port: u16) -> std::io::Result<Vec<TcpListener>> {
+  match (TcpListener::bind((Ipv6Addr::UNSPECIFIED, port)), TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))) {
+    (Ok(v6), Ok(v4)) => Ok(vec![v6, v4]),
+    (Ok(l), Err(_)) | (Err(_), Ok(l)) => Ok(vec![l]),
+    (Err(e), Err(_)) => Err(e),
+  }
+}
+
 
+/// Accept clients on the provided listener, serving each on the pool.
+fn accept(l: TcpListener, pool: &ThreadPool, idle: &Idle, ds_addr: SocketAddr, limits: Limits, keep_alive: Option<Duration>) {
   for s in l.incoming() {  // process each request received
     let stream = match s {
       Ok(i) => i,
       Err(_) => {
-        eprintln!("Terminating malformed TCP connection");
+        warn!("Terminating malformed TCP connection");
         continue;
       },
     };
 
-    let socket = match UdpSocket::bind(&addr) {
-      Ok(s) => s,
-      Err(_) => {
-        eprintln!("Unable to bind a UDP socket to address");
-        return;
-      }
-    };
-    socket.set_read_timeout(Some(SLEEP_TIME)).expect("System doesn't support set_read_timeout. Please update rust to at least v1.4.0.");
-    match socket.connect(&ds_addr) {
-      Ok(_) => (),
-      Err(_) => {
-        eprintln!("Could not connect to datastore address via UDP");
-        return;
-      }
-    }
-
-    handle_error(handle_request(stream, &socket));
+    let idle: Idle = idle.clone();
+    pool.execute(move || handle_client(stream, ds_addr, limits, keep_alive, &idle));
   }
 }
 
 
-/// Handles the provided TcpStream that was initialized
-/// by a client.
-pub fn handle_request(mut stream: TcpStream, socket: &UdpSocket) -> (TcpStream, Result<(), Serr>) {
-  let mut reader: BufReader<&mut TcpStream> = BufReader::new(&mut stream);
-  let mut buf: Vec<u8> = Vec::new();
-  let operation: Op;
-  let mut l: usize;
-  let mut content_length: u64 = 0;
-
-  // Determine the protocol and data being operated on
-  l = read_until_byte(&mut reader, &mut buf, LF);
-  if l > 0 {
-    operation = determine_protocol(&buf);
-  } else {
-    return (stream, Result::Err(Serr::NA));
+/// Resolve the address of the datastore from an IPv4 or IPv6 address,
+/// or a hostname, optionally followed by a port.
+///
+/// IPv6 addresses followed by a port are enclosed in brackets, e.g. [::1]:41000.
+fn resolve(ds: &str) -> Result<SocketAddr, String> {
+  if let Ok(a) = ds.parse::<SocketAddr>() {
+    return Ok(a);
+  }
+  if let Ok(ip) = ds.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+    return Ok(SocketAddr::new(ip, DATASTORE_PORT));
   }
 
-  if operation == Op::NA {
-    return (stream, Result::Err(Serr::NA));
+  let addrs = match ds.rsplit_once(':') {
+    Some((host, port)) => match port.parse::<u16>() {
+      Ok(p) => (host, p).to_socket_addrs(),
+      Err(_) => return Err(format!("invalid datastore port: {}", port)),
+    },
+    None => (ds, DATASTORE_PORT).to_socket_addrs(),
+  };
+
+  match addrs.map(|mut a| a.next()) {
+    Ok(Some(a)) => Ok(a),
+    _ => Err(format!("could not resolve datastore address {}", ds)),
   }
+}
+
+
+/// Serve a client over its own UDP socket to the datastore.
+fn handle_client(stream: TcpStream, ds_addr: SocketAddr, limits: Limits, keep_alive: Option<Duration>, idle: &Idle) {
+  let client: Client = Client { stream: &stream, keep_alive: false, expect_continue: false };
+
+  // bind an ephemeral port of the datastore's address family,
+  // so transfers don't share a socket
+  let local: IpAddr = match ds_addr {
+    SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+    SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+  };
+  let socket = match UdpSocket::bind((local, 0)) {
+    Ok(s) => s,
+    Err(_) => {
+      send_error(&client, Serr::SERVER("Unable to bind a UDP socket to address".to_string()));
+      return;
+    }
+  };
+  match socket.connect(ds_addr) {
+    Ok(_) => (),
+    Err(_) => {
+      send_error(&client, Serr::SERVER("Could not connect to datastore address via UDP".to_string()));
+      return;
+    }
+  }
+
+  handle_request(&stream, &socket, limits, keep_alive, idle);
+}
+
+
+/// Handles the requests sent over the provided TcpStream by a client,
+/// in the order they were sent, until the client closes the connection
+/// or asks for it to be closed.
+///
+/// The connection is closed once idle for the provided time, or after
+/// the first request if no time is provided. While idle, the connection
+/// is parked rather than holding the worker if other clients wait for one.
+pub fn handle_request(stream: &TcpStream, socket: &UdpSocket, limits: Limits, keep_alive: Option<Duration>, idle: &Idle) {
+  let mut reader: BufReader<&TcpStream> = BufReader::new(stream);
 
-  // iterate over lines delinated by line feeds until 
-  // buffer only consists of <CR><LF>
   loop {
-    // split on line feed
-    buf.clear();
-    l = read_until_byte(&mut reader, &mut buf, LF);
-
-    // get content length for POST request
-    if l >= CLEN_LEN {
-      content_length = match fetch_content_len(content_length, &buf) {
-        Ok(i) => i,
-        Err(e) => return (stream, Result::Err(e)),
-      };
+    // pipelined requests may already be buffered, otherwise wait for
+    // the next one no longer than the connection is kept alive
+    if reader.buffer().is_empty() && idle.wait(stream, keep_alive.unwrap_or(limits.patience())) != Wait::READY {
+      return;
     }
 
-    // header fully processed if empty <CR><LF> is read
-    if buf == CRLF {
-      break;
+    // a request that started is read in full without waiting as long
+    let _ = stream.set_read_timeout(Some(limits.patience()));
+    let request: Request = match Request::parse(&mut reader) {
+      Ok(Some(r)) => r,
+      Ok(None) => return,  // closed, or idle for too long
+      Err(e) => {  // where the request ends is unknown, so no other can be read
+        send_error(&Client { stream, keep_alive: false, expect_continue: false }, e);
+        return;
+      },
+    };
+    // a client stalling mid-body would otherwise hold the worker forever,
+    // the transfer is abandoned once the datastore would give up on it
+    let _ = stream.set_read_timeout(Some(limits.patience()));
+
+    // the next request starts after the body, so a body that isn't
+    // read in full leaves the connection to be closed
+    let op: Op = determine_op(&request);
+    let mut body: Body<&mut BufReader<&TcpStream>> = Body::new(&mut reader, request.content_length().unwrap_or_default());
+    let reads_body: bool = matches!(op, Op::POST(_) | Op::PATCH(_));
+    let mut client: Client = Client {
+      stream,
+      keep_alive: keep_alive.is_some() && request.keep_alive() && (reads_body || body.is_done()),
+      expect_continue: reads_body && request.expect_continue(),
+    };
+
+    let r = handle_op(&request, op, &mut body, &client, socket, limits);
+    client.keep_alive &= body.is_done();
+    if let Err(e) = r {
+      send_error(&client, e);
     }
+
+    if !client.keep_alive { return; }
   }
+}
+
 
-  let r = match operation {
+/// Perform the provided operation of the provided request, reading
+/// its body and responding to the client.
+fn handle_op(request: &Request, op: Op, body: &mut Body<&mut BufReader<&TcpStream>>, client: &Client, socket: &UdpSocket, limits: Limits) -> Result<(), Serr> {
+  let content_length: u64 = request.content_length()?;
+
+  // a Range field that can't be parsed is ignored and the whole file is sent
+  let range: Option<Span> = request.field("range").and_then(|r| parse_range(&r));
+
+  // the size of a resumable upload, where data continues it, and its ID
+  let upload_length: Option<u64> = request.field("upload-length").map(|v| parse_upload_field("Upload-Length", &v)).transpose()?;
+  let upload_offset: Option<u64> = request.field("upload-offset").map(|v| parse_upload_field("Upload-Offset", &v)).transpose()?;
+  let upload: Option<u64> = match request.query_param(UPLOAD_PARAM)? {
+    Some(id) => match u64::from_str_radix(&id, 16) {
+      Ok(i) => Some(i),
+      Err(_) => return Err(Serr::BAD(format!("Invalid upload ID {} received", id))),
+    },
+    None => None,
+  };
+
+  // reject malformed paths before asking the datastore,
+  // and only requests about an upload may name one
+  if upload.is_some() && matches!(op, Op::GET(_) | Op::LIST(_) | Op::POST(_)) {
+    return Err(Serr::BAD(format!("{:?} request may not name an upload", request.method)));
+  }
+  match op {
     Op::GET(fetch_filename) => {
-      println!("Receive GET request for {}", fetch_filename);
-      server_handle::handle_get(fetch_filename, &stream, socket)
+      info!("Receive GET request for {}", fetch_filename);
+      relative(&fetch_filename)?;
+      server_handle::handle_get(&fetch_filename, range, client, socket, limits)
+    },
+    Op::LIST(list_dirname) => {
+      info!("Received GET request for directory {}", list_dirname);
+      relative_dir(&list_dirname)?;
+      server_handle::handle_list(&list_dirname, request.field("accept").as_deref(), client, socket, limits)
     },
     Op::POST(upload_filename) => {
-      println!("Received POST request for {}", upload_filename);
-      // read file from stream and save, respective avaiable memory
-      let mut file = match File::create(&upload_filename) {
-        Ok(f) => f,
-        Err(e) => return (stream, Result::Err(Serr::SERVER(format!("Couldn't create file {}:\n{}", &upload_filename, e)))),
-      };
+      info!("Received POST request for {}", upload_filename);
+      relative(&upload_filename)?;
 
-      let mut buf: [u8; MTU];
-      let mut amt: usize;
-      let mut total_read: u64 = 0;
-      while total_read != content_length {
-        buf = [0; MTU];
-        amt = match reader.read(&mut buf) {
-          Ok(i) => i,
-          Err(e) => return (stream, Result::Err(Serr::SERVER(format!("Couldn't read from stream:\n{}", e)))),
-        };
-
-        if (amt == 0) && (total_read != content_length) {
-          // ensure total read isn't less than OR greater than content_length
-          return (stream, Result::Err(Serr::SERVER(format!("Invalid net amount of data read from stream ({}), should be {} for file {}", total_read, content_length, &upload_filename))));
+      // an Upload-Length field starts a resumable upload, continued by PATCH requests
+      if let Some(length) = upload_length {
+        if content_length > 0 {
+          return Err(Serr::BAD("Resumable uploads are started without data".to_string()));
         }
-
-        match file.write(&buf[0..amt]) {
-          Ok(_) => (),
-          Err(e) => return (stream, Result::Err(Serr::SERVER(format!("Couldn't save buf to {}:\n{}", &upload_filename, e)))),
-        }
-        total_read += amt as u64;
+        server_handle::handle_create(&upload_filename, length, client, socket, limits)
+      } else {
+        // forward the body to the datastore as it's read from the stream
+        server_handle::handle_post(&upload_filename, content_length, body, client, socket, limits)
       }
+    },
+    Op::PATCH(patch_filename) => {
+      info!("Received PATCH request for {}", patch_filename);
+      relative(&patch_filename)?;
+      let (id, offset) = match (upload, upload_offset) {
+        (Some(id), Some(offset)) => (id, offset),
+        _ => return Err(Serr::BAD(format!("PATCH of {} names no upload and offset", patch_filename))),
+      };
 
-      server_handle::handle_post(upload_filename, content_length, &stream, socket)
+      server_handle::handle_patch(&patch_filename, id, offset, content_length, body, client, socket, limits)
+    },
+    Op::DELETE(delete_filename) => {
+      info!("Received DELETE request for {}", delete_filename);
+      relative(&delete_filename)?;
+
+      // a DELETE of an upload abandons it
+      match upload {
+        Some(id) => server_handle::handle_abort(&delete_filename, id, client, socket, limits),
+        None => server_handle::handle_delete(&delete_filename, client, socket, limits),
+      }
+    },
+    Op::HEAD(stat_filename) => {
+      info!("Received HEAD request for {}", stat_filename);
+      relative(&stat_filename)?;
+
+      // a HEAD of an upload answers how much of it was received
+      match upload {
+        Some(id) => server_handle::handle_upload_head(&stat_filename, id, client, socket, limits),
+        None => server_handle::handle_head(&stat_filename, client, socket, limits),
+      }
     },
     Op::NA => Result::Err(Serr::NA),
-  };
+  }
+}
+
 
-  match r {
-    Ok(_) => (stream, Result::Ok(())),
-    Err(e) => (stream, Result::Err(e)),
+/// Parse the value of an Upload-Length or Upload-Offset field.
+fn parse_upload_field(name: &str, value: &str) -> Result<u64, Serr> {
+  match value.parse::<u64>() {
+    Ok(i) => Ok(i),
+    Err(_) => Err(Serr::BAD(format!("Invalid {} {} received", name, value))),
   }
 }
 
 
-/// Fetch the value stored in the Content-Length field of the HTTP header,
-/// if the buffer is the Content-Length field.
-fn fetch_content_len(curr_cl: u64, buf: &Vec<u8>) -> Result<u64, Serr> {
-  if buf[0..CLEN_LEN] == CLEN {
-    return
-    match bytes_to_str(buf, CLEN_LEN, buf.len() - 2)  // account for trailing <CR><LF>
-    .parse::<u64>()
-    {
-      Ok(i) => Result::Ok(i),
-      Err(_) => {
-        return Result::Err(Serr::SERVER("Invalid Content-Length received, terminating TCP stream.".to_string()));
-      },
-    };
+/// Parse the span of a file requested by the value of a Range field,
+/// e.g. bytes=0-499, bytes=500- or bytes=-500.
+///
+/// Returns None if the value isn't a single range of bytes.
+fn parse_range(value: &str) -> Option<Span> {
+  let unit: &str = value.get(..BYTES_UNIT.len())?;
+  if !unit.eq_ignore_ascii_case(BYTES_UNIT) { return None; }
+
+  let (first, last) = value[BYTES_UNIT.len()..].trim().split_once('-')?;
+  let last: Option<u64> = match last.trim() {
+    "" => None,
+    l => Some(l.parse::<u64>().ok()?),
+  };
 
-  } else {
-    return Result::Ok(curr_cl);
+  match (first.trim(), last) {
+    ("", Some(l)) => Some(Span { offset: None, length: Some(l) }),
+    ("", None) => None,
+    (f, l) => {
+      // an offset of u64::MAX is taken for a suffix, and is past any file either way
+      let first: u64 = f.parse::<u64>().ok()?.min(u64::MAX - 1);
+      match l {
+        Some(l) if l < first => None,
+        Some(l) => Some(Span { offset: Some(first), length: Some((l - first).saturating_add(1)) }),
+        None => Some(Span { offset: Some(first), length: None }),
+      }
+    },
   }
 }
 
 
-fn read_until_byte<T: std::io::Read>(reader: &mut BufReader<&mut T>, buf: &mut Vec<u8>, byte: u8) -> usize {
-  match reader.read_until(byte, buf) {
-    Ok(i) => i,
-    Err(_) => 0,
+/// Determines the operation of the provided request, on the path relative
+/// to where files are stored.
+fn determine_op(request: &Request) -> Op {
+  let path: String = format!(".{}", request.path);
+
+  match request.method {
+    // a path ending in a slash names a directory to list
+    Method::GET if path.ends_with('/') => Op::LIST(path),
+    Method::GET => Op::GET(path),
+    Method::POST => Op::POST(path),
+    Method::HEAD => Op::HEAD(path),
+    Method::PATCH => Op::PATCH(path),
+    Method::DELETE => Op::DELETE(path),
+    Method::OTHER(_) => Op::NA,
   }
 }
 
 
-/// Determines the HTTP protocol in use, if any.
-fn determine_protocol(data: &Vec<u8>) -> Op {
-  let end: usize = data.len() - 2;
-  let p: &[u8] = &data[end - HTTP_LEN..end];
-  let path_end = end - HTTP_LEN - 1;  // Remove the space between path and HTTP version
+/// Send the respective error for the server error to the client.
+fn send_error(client: &Client, serr: Serr) {
+  let err_msg: String = match serr {
+    Serr::DNE(e) => { send_404_error(client); e},
+    Serr::FORBIDDEN(e) => { send_403_error(client); e},
+    Serr::BAD(e) => { send_400_error(client); e},
+    Serr::SERVER(e) => { send_500_error(client); e},
+    Serr::CONFLICT(e) => { send_409_error(client); e},
+    Serr::RANGE(e) => { send_416_error(client); e},
+    Serr::TIMEOUT(e) => { send_504_error(client); e},
+    Serr::NA => { send_501_error(client); "Unsupported request received.".to_string() },
+  };
+  error!("{}", err_msg);
+}
 
-  // check end is valid http version: http/1.0 or http/1.1
-  if (p != HTTP10) && (p != HTTP11) {
-    return Op::NA;
-  }
 
-  if data[0..LEN_GET] == GET {
-    return Op::GET(format!(".{}", bytes_to_str(data, LEN_GET + 1, path_end)));
+/// Send an HTTP Error 400 to the client.
+fn send_400_error(client: &Client) {
+  respond(ERROR_400, client, "Interrupted while sending 400 response");
+}
 
-  } else if data[0..LEN_POST] == POST {
-    return Op::POST(format!(".{}", bytes_to_str(data, LEN_POST + 1, path_end)));
 
-  } else {
-    return Op::NA;
-  }
+/// Send an HTTP Error 403 to the client.
+fn send_403_error(client: &Client) {
+  respond(ERROR_403, client, "Interrupted while sending 403 response");
 }
 
 
-/// Creates a string from a designated slice of a byte buffer.
-fn bytes_to_str(buf: &[u8], start: usize, end: usize) -> String {
-  buf[start..end]
-    .into_iter()
-    .map(|b| *b as char)
-    .collect::<String>()
+/// Send an HTTP Error 404 to the client.
+fn send_404_error(client: &Client) {
+  respond(ERROR_404, client, "Interrupted while sending 404 response");
 }
 
 
-/// Send an error to the proxy if an error occurs.
-fn handle_error(result: (TcpStream, Result<(), Serr>)) {
-  match result.1 {
-    Ok(_) => (),
-    Err(e) => send_error(&result.0, e),
-  }
+/// Send an HTTP Error 409 to the client.
+fn send_409_error(client: &Client) {
+  respond(ERROR_409, client, "Interrupted while sending 409 response");
 }
 
 
-/// Send the respective error for the server error over the
-/// provided socket.
-fn send_error(stream: &TcpStream, serr: Serr) {
-  let err_msg: String = match serr {
-    Serr::DNE(e) => { send_404_error(stream); e},
-    Serr::SERVER(e) => { send_500_error(stream); e},
-    Serr::NA => format!("Unsupported request received."),
-  };
-  eprintln!("{}", err_msg);
+/// Send an HTTP Error 416 to the client.
+fn send_416_error(client: &Client) {
+  respond(ERROR_416, client, "Interrupted while sending 416 response");
+}
+
+
+/// Send an HTTP Error 500 to the client.
+fn send_500_error(client: &Client) {
+  respond(ERROR_500, client, "Interrupted while sending 500 response");
 }
 
 
-/// Send an HTTP Error 404 over the provided stream.
-fn send_404_error(stream: &TcpStream) {
-  respond(ERROR_404, stream, "Interrupted while sending 404 response");
+/// Send an HTTP Error 501 to the client.
+fn send_501_error(client: &Client) {
+  respond(ERROR_501, client, "Interrupted while sending 501 response");
 }
 
 
-/// Send an HTTP Error 500 over the provided stream.
-fn send_500_error(stream: &TcpStream) {
-  respond(ERROR_500, stream, "Interrupted while sending 500 response");
+/// Send an HTTP Error 504 to the client.
+fn send_504_error(client: &Client) {
+  respond(ERROR_504, client, "Interrupted while sending 504 response");
 }
 
 
-/// Send bytes over a stream, and print the provided error msg to stderr if
-/// an error occurs.
-fn respond(buf: &[u8], mut stream: &TcpStream, err_msg: &str) {
-  match stream.write_all(buf) {
+/// Send a response to the client, and print the provided error msg to stderr
+/// if an error occurs.
+///
+/// The response's status line is followed by a Connection field,
+/// telling the client if the connection is kept open.
+fn respond(buf: &[u8], client: &Client, err_msg: &str) {
+  let mut stream: &TcpStream = client.stream;
+  let status_end: usize = buf.windows(CRLF.len()).position(|w| w == CRLF).map_or(buf.len(), |i| i + CRLF.len());
+  let connection: &[u8] = if client.keep_alive { KEEP_ALIVE } else { CLOSE };
+
+  match stream.write_all(&[&buf[..status_end], connection, &buf[status_end..]].concat()) {
     Ok(_) => (),
-    Err(_) => eprintln!("{}", err_msg),
+    Err(_) => warn!("{}", err_msg),
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  #[test]
+  fn closed_range() {
+    assert_eq!(parse_range("bytes=0-99"), Some(Span { offset: Some(0), length: Some(100) }));
+    assert_eq!(parse_range("bytes=5-5"), Some(Span { offset: Some(5), length: Some(1) }));
+    assert_eq!(parse_range("Bytes= 2 - 3"), Some(Span { offset: Some(2), length: Some(2) }));
+  }
+
+
+  #[test]
+  fn open_ended_range() {
+    assert_eq!(parse_range("bytes=10-"), Some(Span { offset: Some(10), length: None }));
+    assert_eq!(parse_range("bytes=0-"), Some(Span { offset: Some(0), length: None }));
+  }
+
+
+  #[test]
+  fn suffix_range() {
+    assert_eq!(parse_range("bytes=-500"), Some(Span { offset: None, length: Some(500) }));
+    assert_eq!(parse_range("bytes=-0"), Some(Span { offset: None, length: Some(0) }));
+
+    // a suffix of no bytes holds no byte of any file
+    assert_eq!(parse_range("bytes=-0").and_then(|s| s.within(10)), None);
+  }
+
+
+  #[test]
+  fn invalid_ranges_are_ignored() {
+    for value in ["bytes=5-4", "bytes=-", "bytes=a-b", "bytes=0-1,4-5", "items=0-1", "bytes", ""] {
+      assert_eq!(parse_range(value), None, "{}", value);
+    }
+  }
+
+
+  #[test]
+  fn empty_file_without_range_is_whole() {
+    assert_eq!(Span { offset: Some(0), length: None }.within(0), Some((0, 0)));
+    assert_eq!(parse_range("bytes=0-").and_then(|s| s.within(0)), Some((0, 0)));
+    assert_eq!(parse_range("bytes=0-0").and_then(|s| s.within(0)), None);
   }
 }
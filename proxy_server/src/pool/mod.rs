@@ -0,0 +1,87 @@
+use std::{sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}, mpsc::{Receiver, SyncSender, sync_channel}}, thread::{self, JoinHandle}, panic::{self, AssertUnwindSafe}};
+
+/// A unit of work run by a worker.
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+
+/// A fixed number of worker threads running jobs from a bounded queue.
+///
+/// Once every worker is busy and the queue is full, submitting a job
+/// blocks until a worker frees up.
+pub struct ThreadPool {
+  workers: Vec<JoinHandle<()>>,
+  sender: Option<SyncSender<Job>>,
+  pending: Arc<AtomicUsize>,  // jobs submitted that no worker took yet
+}
+
+
+impl ThreadPool {
+  /// Create a pool of the provided number of workers, queueing
+  /// at most that many pending jobs.
+  pub fn new(size: usize) -> ThreadPool {
+    let (tx, rx) = sync_channel::<Job>(size);
+    let rx: Arc<Mutex<Receiver<Job>>> = Arc::new(Mutex::new(rx));
+    let pending: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let mut workers: Vec<JoinHandle<()>> = Vec::with_capacity(size);
+
+    for _ in 0..size {
+      let rx = Arc::clone(&rx);
+      let pending = Arc::clone(&pending);
+      workers.push(thread::spawn(move || work(rx, pending)));
+    }
+
+    ThreadPool { workers, sender: Some(tx), pending }
+  }
+
+
+  /// Get the count of jobs submitted that are waiting for a worker,
+  /// including those blocked waiting to be queued.
+  pub fn pending(&self) -> Arc<AtomicUsize> {
+    Arc::clone(&self.pending)
+  }
+
+
+  /// Queue a job to be run by the next free worker.
+  pub fn execute<F: FnOnce() + Send + 'static>(&self, f: F) {
+    if let Some(tx) = &self.sender {
+      self.pending.fetch_add(1, Ordering::Relaxed);
+      if tx.send(Box::new(f)).is_err() {
+        self.pending.fetch_sub(1, Ordering::Relaxed);
+        protocol::error!("Every worker has stopped, dropping job");
+      }
+    }
+  }
+}
+
+
+impl Drop for ThreadPool {
+  /// Let the workers finish the queued jobs, then wait for them to exit.
+  fn drop(&mut self) {
+    drop(self.sender.take());
+
+    for worker in self.workers.drain(..) {
+      let _ = worker.join();
+    }
+  }
+}
+
+
+/// Run jobs until the pool is dropped.
+///
+/// A job that panics only loses its own work, the worker moves on.
+fn work(rx: Arc<Mutex<Receiver<Job>>>, pending: Arc<AtomicUsize>) {
+  loop {
+    let job: Job = match rx.lock() {
+      Ok(r) => match r.recv() {
+        Ok(j) => j,
+        Err(_) => return,  // pool dropped
+      },
+      Err(_) => return,  // another worker panicked while waiting
+    };
+    pending.fetch_sub(1, Ordering::Relaxed);
+
+    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+      protocol::warn!("Worker recovered from a panicking job");
+    }
+  }
+}
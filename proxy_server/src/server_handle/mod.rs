@@ -1,6 +1,8 @@
-use std::{net::{TcpStream, UdpSocket}, fs::{File, remove_file}, io::Read};
+use std::{net::{UdpSocket, Shutdown}, io::{self, Read, ErrorKind}};
 
-use crate::{Serr, respond, protocol::{create_pkt, FLAGS_LEN, SEQ_LEN, GET, SYNACK, BODY_LEN, receive::receive, SLEEP_TIME, send_buf, FLAG_404, POST, ACK, send::send, FLAG_500, filename_as_body}, MTU, CRLF};
+use protocol::{connection::{Connection, Limits}, listing::{self, Entry}, info, CRLF, GET, POST, DELETE, STAT, LIST, CREATE, OFFSET, PATCH, ABORT, SYNACK, ACK, FLAG_500, Serr, Span, create_header, create_pkt, span_body, patch_body, get_synack, get_length, get_seq, get_mtu, get_stat, new_conn_id, send_buf, filename_as_body, send::send, receive::receive};
+
+use crate::{respond, http::{Client, percent_encode}, listing::{self as render, HTML, JSON}};
 
 /// ASCII values for Location: 
 const LOC: [u8; 10] = [76, 111, 99, 97, 116, 105, 111, 110, 58, 32];
@@ -8,13 +10,60 @@ const LOC: [u8; 10] = [76, 111, 99, 97, 116, 105, 111, 110, 58, 32];
 /// Success 200 response
 const OK_200: &[u8] = "HTTP/1.1 200 OK\r\n".as_bytes();
 
+/// Success 206 response
+const PARTIAL_206: &[u8] = "HTTP/1.1 206 PARTIAL CONTENT\r\n".as_bytes();
+
 /// Success 201 response
-const CREATED_201: &[u8] = "HTTP/1.1 200 CREATED\r\n".as_bytes();
+const CREATED_201: &[u8] = "HTTP/1.1 201 CREATED\r\n".as_bytes();
+
+/// Success 204 response
+const NO_CONTENT_204: &[u8] = "HTTP/1.1 204 NO CONTENT\r\n\r\n".as_bytes();
+
+/// Success 204 response, followed by fields
+const NO_CONTENT_204_FIELDS: &[u8] = "HTTP/1.1 204 NO CONTENT\r\n".as_bytes();
+
+/// Last-Modified field of a response
+const LAST_MODIFIED: &[u8] = "Last-Modified: ".as_bytes();
+
+/// ETag field of a response
+const ETAG: &[u8] = "ETag: ".as_bytes();
+
+/// Accept-Ranges field of a response, advertising support for Range fields
+const ACCEPT_RANGES: &[u8] = "Accept-Ranges: bytes\r\n".as_bytes();
+
+/// Content-Range field of a response
+const CONTENT_RANGE: &[u8] = "Content-Range: ".as_bytes();
+
+/// Upload-Offset field of a response
+const UPLOAD_OFFSET: &[u8] = "Upload-Offset: ".as_bytes();
+
+/// Upload-Length field of a response
+const UPLOAD_LENGTH: &[u8] = "Upload-Length: ".as_bytes();
+
+/// Fields of every response about a resumable upload, naming the version of
+/// the tus protocol followed and keeping caches from storing its offset
+const TUS_RESUMABLE: &[u8] = "Tus-Resumable: 1.0.0\r\nCache-Control: no-store\r\n".as_bytes();
+
+/// Content-Type field of a response
+const CONTENT_TYPE: &[u8] = "Content-Type: ".as_bytes();
+
+/// Abbreviated days of the week, starting from the Thursday of the Unix epoch
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+
+/// Abbreviated months of the year
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
 
 /// Two sets of Carriage-Returns and Line Feeds
 const DOUBLE_CRLF: &[u8] = "\r\n\r\n".as_bytes();
 
 
+/// Get the absolute path of the provided file relative to where files are
+/// stored, as a client requests it, e.g. /sub/f.bin for ./sub/f.bin.
+fn location(filename: &str) -> &str {
+  filename.strip_prefix('.').unwrap_or(filename)
+}
+
+
 /// Print the contents of a byte buffer.
 /// Debugging tool.
 fn _print_buf(buf: &[u8]) {
@@ -24,123 +73,334 @@ fn _print_buf(buf: &[u8]) {
 }
 
 
-/// Sends a datagram over the UDP socket that is connected
-/// to the datastore server.
+/// Responds to an HTTP GET request, streaming the file to the client
+/// as it's received from the datastore.
+///
+/// A requested span of the file is answered with 206 Partial Content,
+/// and only the span is fetched.
 /// 
-/// Sends an error back to the client based on the operation being performed.
-fn send_datagram(buf: &[u8], socket: &UdpSocket) -> Result<usize, Serr> {
-  match socket.send(buf) {
-    Ok(i) => Result::Ok(i),
-    Err(_) => Result::Err(Serr::SERVER("UDP socket is not connected, cannot read from UDP socket".to_string()))
+/// Message format: {"GET", "/path/parts", "more/if/spaces", ..., "HTTP/1.1"}
+pub fn handle_get(filename: &str, range: Option<Span>, client: &Client, socket: &UdpSocket, limits: Limits) -> Result<(), Serr> {
+  let mut buf: Vec<u8>;
+  let length: u64;
+  let mut conn: Connection = Connection::with_limits(socket, new_conn_id(), limits);
+
+  // request = [&GET.to_be_bytes(), id.to_be_bytes(), 0u64.to_be_bytes(), filename.as_bytes(), &crate::CRLF, offset, length]
+  let data: Vec<u8> = span_body(filename, &range.unwrap_or(Span { offset: Some(0), length: None }))?;
+  buf = create_pkt(GET, conn.id, 0, &data);
+
+  // send request until Flags = 160 (syn & ack), or the datastore answers 416
+  buf = send_buf(&mut conn, &buf, SYNACK, filename)?;
+
+  // get length of the span from this ack (seq #), and where it starts in the file
+  length = get_seq(&buf)?;
+  let (offset, size) = get_synack(&buf)?;
+
+  // <OK_200>Accept-Ranges: bytes\r\nContent-Length: <length>\r\n\r\n<buf>, or
+  // <PARTIAL_206>Content-Range: bytes <first>-<last>/<size>\r\nContent-Length: <length>\r\n\r\n<buf>
+  // a range of an empty file holds no byte to describe, so the whole file is sent
+  let status: Vec<u8> = match range {
+    Some(_) if length > 0 => [PARTIAL_206, CONTENT_RANGE, format!("bytes {}-{}/{}", offset, offset + length - 1, size).as_bytes(), &CRLF].concat(),
+    _ => [OK_200, ACCEPT_RANGES].concat(),
+  };
+  let response: &Vec<u8> = &[&status, &crate::CLEN[..], length.to_string().as_bytes(), DOUBLE_CRLF].concat();
+  respond(response, client, "Interrupted while responding to a GET request");
+
+  // receive data straight into the response, once the response
+  // started a failed transfer can only be cut short
+  if let Err(e) = receive(&mut conn, client.stream, filename, length) {
+    let _ = client.stream.shutdown(Shutdown::Both);
+    return Err(e);
   }
+
+  info!("Successfully responded to {} GET", filename);
+  Result::Ok(())
 }
 
 
-/// Read a datagram from the UDP socket, return the number
-/// of bytes and the content buffer.
-/// 
-/// Sends an error back to the client based on the operation being performed.
-fn read_datagram(socket: &UdpSocket) -> Result<(usize, [u8; MTU]), Serr> {
-  let mut buf: [u8; MTU] = [0; MTU];
-
-  match socket.recv(&mut buf) {
-    Ok(amt) => Result::Ok((amt, buf)),
-    Err(_) => {
-      Result::Err(Serr::SERVER("UDP socket is not connected, cannot read from UDP socket".to_string()))
-    }
+/// Responds to an HTTP GET request for a directory with a listing of its
+/// entries, as HTML if the client's Accept field prefers it over JSON.
+pub fn handle_list(filename: &str, accept: Option<&str>, client: &Client, socket: &UdpSocket, limits: Limits) -> Result<(), Serr> {
+  let mut buf: Vec<u8>;
+  let size: u64;
+  let mut conn: Connection = Connection::with_limits(socket, new_conn_id(), limits);
+
+  // request = syn list id seq#=0 body=directory
+  let data: Vec<u8> = filename_as_body(filename)?;
+  buf = create_pkt(LIST, conn.id, 0, &data);
+
+  // send request until Flags = 160 (syn & ack), holding the listing's size as its seq #
+  buf = send_buf(&mut conn, &buf, SYNACK, filename)?;
+  size = get_seq(&buf)?;
+
+  // the listing is sent like a file, and rendered once received in full
+  let mut encoded: Vec<u8> = Vec::new();
+  receive(&mut conn, &mut encoded, filename, size)?;
+  let entries: Vec<Entry> = listing::decode(&encoded)?;
+
+  let path: &str = location(filename);
+  let (media, body): (&str, String) = match render::prefers_html(accept) {
+    true => (HTML, render::html(path, &entries)),
+    false => (JSON, render::json(path, &entries)),
+  };
+
+  // <OK_200>Content-Type: <media>; charset=utf-8\r\nContent-Length: <size>\r\n\r\n<body>
+  let response: &Vec<u8> = &[
+    OK_200, CONTENT_TYPE, media.as_bytes(), b"; charset=utf-8", &CRLF,
+    &crate::CLEN, body.len().to_string().as_bytes(), DOUBLE_CRLF, body.as_bytes(),
+  ].concat();
+  respond(response, client, "Interrupted while responding to a listing request");
+
+  info!("Successfully responded to {} listing", filename);
+  Result::Ok(())
+}
+
+
+/// Responds to an HTTP POST request, sending the body to the
+/// datastore as it's read.
+pub fn handle_post<R: Read>(filename: &str, length: u64, body: R, client: &Client, socket: &UdpSocket, limits: Limits) -> Result<(), Serr> {
+  // request = syn post id seq#=len body=filename
+  let mut conn: Connection = Connection::with_limits(socket, new_conn_id(), limits);
+  let data: Vec<u8> = filename_as_body(filename)?;
+  let mut buf: Vec<u8> = create_pkt(POST, conn.id, length, &data);
+  // send request until Flags = 128 (ack)
+  buf = send_buf(&mut conn, &buf, ACK, filename)?;
+
+  // the ack carries the largest datagram the datastore accepts
+  conn.negotiate(get_mtu(&buf)?);
+
+  // the datastore accepted the upload, so the body is wanted
+  client.proceed();
+
+  // let the datastore stop waiting for the rest of a body cut short,
+  // dropping what it received
+  if let Err(e) = send(&mut conn, body, filename) {
+    let _ = conn.send(&create_header(FLAG_500, conn.id, 0));
+    return Err(e);
   }
+
+  // <CREATED_201>Location: <path>\r\nContent-Length: 0\r\n\r\n
+  let response: &Vec<u8> = &[CREATED_201, &LOC, percent_encode(location(filename)).as_bytes(), &CRLF, &crate::CLEN, b"0", DOUBLE_CRLF].concat();
+  respond(response, client, "Interrupted while responding to a POST request");
+
+  info!("Successfully responded to {} POST", filename);
+  Result::Ok(())
 }
 
 
-/// Get sequence number as a u64.
-fn get_seq(buf: &[u8; MTU]) -> Result<u64, Serr> {
-  let bytes = buf[FLAGS_LEN..FLAGS_LEN + SEQ_LEN]
-  .try_into();
+/// Responds to an HTTP DELETE request.
+pub fn handle_delete(filename: &str, client: &Client, socket: &UdpSocket, limits: Limits) -> Result<(), Serr> {
+  // request = syn delete id seq#=0 body=filename
+  let mut conn: Connection = Connection::with_limits(socket, new_conn_id(), limits);
+  let data: Vec<u8> = filename_as_body(filename)?;
+  let buf: Vec<u8> = create_pkt(DELETE, conn.id, 0, &data);
 
-  return match bytes {
-    Ok(i) => Ok(u64::from_be_bytes(i)),
-    Err(_) => Err(Serr::SERVER(format!("out of bounds: there were not 8 bytes between starting index {} and end of buffer of size {}", FLAGS_LEN, buf.len())))
-  };
+  // send request until Flags = 128 (ack), or the datastore answers 404
+  send_buf(&mut conn, &buf, ACK, filename)?;
+
+  respond(NO_CONTENT_204, client, "Interrupted while responding to a DELETE request");
+  info!("Successfully responded to {} DELETE", filename);
+  Result::Ok(())
 }
 
 
-/// Responds to an HTTP GET request.
-/// 
-/// Message format: {"GET", "/path/parts", "more/if/spaces", ..., "HTTP/1.1"}
-pub fn handle_get(filename: String, stream: &TcpStream, socket: &UdpSocket) -> Result<(), Serr> {
-  let mut buf: [u8; MTU];
-  let size: u64;
+/// Responds to an HTTP HEAD request with the size, modification time
+/// and digest of the file, without fetching it.
+pub fn handle_head(filename: &str, client: &Client, socket: &UdpSocket, limits: Limits) -> Result<(), Serr> {
+  // request = syn stat id seq#=0 body=filename
+  let mut conn: Connection = Connection::with_limits(socket, new_conn_id(), limits);
+  let data: Vec<u8> = filename_as_body(filename)?;
+  let mut buf: Vec<u8> = create_pkt(STAT, conn.id, 0, &data);
+
+  // send request until Flags = 128 (ack), holding size as its seq #
+  buf = send_buf(&mut conn, &buf, ACK, filename)?;
+  let size: u64 = get_seq(&buf)?;
+  let (mtime, digest) = get_stat(&buf)?;
+  let etag: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+
+  // <OK_200>Content-Length: <size>\r\nLast-Modified: <date>\r\nETag: "<digest>"\r\n\r\n
+  let response: &Vec<u8> = &[
+    OK_200, ACCEPT_RANGES, &crate::CLEN, size.to_string().as_bytes(), &CRLF,
+    LAST_MODIFIED, http_date(mtime).as_bytes(), &CRLF,
+    ETAG, format!("\"{}\"", etag).as_bytes(), DOUBLE_CRLF,
+  ].concat();
+  respond(response, client, "Interrupted while responding to a HEAD request");
+
+  info!("Successfully responded to {} HEAD", filename);
+  Result::Ok(())
+}
 
-  // request = [&GET.to_be_bytes(), 0u64.to_be_bytes(), filename.as_bytes(), &crate::CRLF]
-  let data: [u8; BODY_LEN] = filename_as_body(&filename)?;
-  buf = create_pkt(GET, 0, &data);
 
-  // send request until Flags = 160 (syn & ack)
-  buf = send_buf(socket, &buf, SYNACK, &filename)?;
+/// Responds to an HTTP POST request holding an Upload-Length field by
+/// starting a resumable upload of the file, answering with its location.
+pub fn handle_create(filename: &str, length: u64, client: &Client, socket: &UdpSocket, limits: Limits) -> Result<(), Serr> {
+  // request = syn create id seq#=len body=filename
+  let mut conn: Connection = Connection::with_limits(socket, new_conn_id(), limits);
+  let data: Vec<u8> = filename_as_body(filename)?;
+  let mut buf: Vec<u8> = create_pkt(CREATE, conn.id, length, &data);
 
-  // get length from this ack (seq #)
-  size = get_seq(&buf)?;
+  // send request until Flags = 128 (ack), holding the upload's ID as its seq #
+  buf = send_buf(&mut conn, &buf, ACK, filename)?;
+  let id: u64 = get_seq(&buf)?;
 
-  // receive data
-  receive(socket, filename.clone(), size)?;
+  // <CREATED_201>Location: <path>?upload=<id>\r\nUpload-Offset: 0\r\n<TUS_RESUMABLE>Content-Length: 0\r\n\r\n
+  let response: &Vec<u8> = &[
+    CREATED_201, &LOC, format!("{}?upload={:016x}", percent_encode(location(filename)), id).as_bytes(), &CRLF,
+    UPLOAD_OFFSET, b"0", &CRLF, TUS_RESUMABLE, &crate::CLEN, b"0", DOUBLE_CRLF,
+  ].concat();
+  respond(response, client, "Interrupted while responding to a POST request");
 
-  // <OK_200>Content-Length: <size>\r\n\r\n<buf>
-  let response: &Vec<u8> = &[OK_200, &crate::CLEN, size.to_string().as_bytes(), DOUBLE_CRLF].concat();
-  respond(&response, &stream, "Interrupted while responding to a GET request");
-  send_file_to_client(&filename, stream)?;
-  remove_file(&filename);
+  info!("Successfully started upload {:016x} of {}", id, filename);
+  Result::Ok(())
+}
+
+
+/// Responds to an HTTP HEAD request of a resumable upload with the
+/// number of bytes received so far and the size of the whole file.
+pub fn handle_upload_head(filename: &str, id: u64, client: &Client, socket: &UdpSocket, limits: Limits) -> Result<(), Serr> {
+  // request = syn offset id seq#=upload id body=filename
+  let mut conn: Connection = Connection::with_limits(socket, new_conn_id(), limits);
+  let data: Vec<u8> = filename_as_body(filename)?;
+  let mut buf: Vec<u8> = create_pkt(OFFSET, conn.id, id, &data);
+
+  // send request until Flags = 128 (ack), holding the offset as its seq #
+  buf = send_buf(&mut conn, &buf, ACK, filename)?;
+  let offset: u64 = get_seq(&buf)?;
+  let length: u64 = get_length(&buf)?;
+
+  // <OK_200>Upload-Offset: <offset>\r\nUpload-Length: <length>\r\n<TUS_RESUMABLE>\r\n
+  let response: &Vec<u8> = &[
+    OK_200, UPLOAD_OFFSET, offset.to_string().as_bytes(), &CRLF,
+    UPLOAD_LENGTH, length.to_string().as_bytes(), &CRLF, TUS_RESUMABLE, &CRLF,
+  ].concat();
+  respond(response, client, "Interrupted while responding to a HEAD request");
 
-  println!("Successfully responded to {} GET", filename);
+  info!("Successfully responded to upload {:016x} of {} HEAD", id, filename);
   Result::Ok(())
 }
 
 
-/// Responds to an HTTP POST request.
-pub fn handle_post(filename: String, length: u64, stream: &TcpStream, socket: &UdpSocket) -> Result<(), Serr> {
-  let file: File = match File::open(&filename) {
-    Ok(f) => f,
-    Err(e) => return Err(Serr::SERVER(format!("could not open {}:\n{}", filename, e))),
-  };
+/// Responds to an HTTP DELETE request of a resumable upload by abandoning
+/// it, leaving the file it would have replaced in place.
+pub fn handle_abort(filename: &str, id: u64, client: &Client, socket: &UdpSocket, limits: Limits) -> Result<(), Serr> {
+  // request = syn abort id seq#=upload id body=filename
+  let mut conn: Connection = Connection::with_limits(socket, new_conn_id(), limits);
+  let data: Vec<u8> = filename_as_body(filename)?;
+  let buf: Vec<u8> = create_pkt(ABORT, conn.id, id, &data);
 
-  // request = syn post seq#=len body=filename
-  let data: [u8; BODY_LEN] = filename_as_body(&filename)?;
-  let buf: [u8; MTU] = create_pkt(POST, length, &data);
-  // send request until Flags = 128 (ack)
-  send_buf(socket, &buf, ACK, &filename)?;
+  // send request until Flags = 128 (ack), or the datastore answers 404 or 409
+  send_buf(&mut conn, &buf, ACK, filename)?;
+
+  // <NO_CONTENT_204_FIELDS><TUS_RESUMABLE>\r\n
+  let response: &Vec<u8> = &[NO_CONTENT_204_FIELDS, TUS_RESUMABLE, &CRLF].concat();
+  respond(response, client, "Interrupted while responding to a DELETE request");
 
-  // call send
-  send(socket, file, filename.clone(), length)?;
+  info!("Successfully abandoned upload {:016x} of {}", id, filename);
+  Result::Ok(())
+}
 
-  // <CREATED_201>Location: <filename>\r\nContent-Length: <size>\r\n\r\n<buf>
-  let response: &Vec<u8> = &[CREATED_201, &LOC, filename.as_bytes(), &crate::CRLF, &crate::CLEN, length.to_string().as_bytes(), DOUBLE_CRLF].concat();
-  respond(&response, &stream, "Interrupted while responding to a POST request");
-  send_file_to_client(&filename, stream)?;
-  remove_file(&filename);
 
-  println!("Successfully responded to {} POST", filename);
+/// Responds to an HTTP PATCH request, sending the body to the datastore
+/// as it's read to continue a resumable upload from the provided offset.
+///
+/// If the body is cut short, the datastore keeps what it received
+/// and the upload can be continued from there.
+#[allow(clippy::too_many_arguments)]
+pub fn handle_patch<R: Read>(filename: &str, id: u64, offset: u64, length: u64, body: R, client: &Client, socket: &UdpSocket, limits: Limits) -> Result<(), Serr> {
+  // request = syn patch id seq#=len body=filename, upload id, offset
+  let mut conn: Connection = Connection::with_limits(socket, new_conn_id(), limits);
+  let data: Vec<u8> = patch_body(filename, id, offset)?;
+  let mut buf: Vec<u8> = create_pkt(PATCH, conn.id, length, &data);
+
+  // send request until Flags = 128 (ack), or the datastore answers 409
+  buf = send_buf(&mut conn, &buf, ACK, filename)?;
+  conn.negotiate(get_mtu(&buf)?);
+  client.proceed();
+
+  // let the datastore stop waiting for the rest of a body cut short
+  if let Err(e) = send(&mut conn, body, filename) {
+    let _ = conn.send(&create_header(FLAG_500, conn.id, 0));
+    return Err(e);
+  }
+
+  // <NO_CONTENT_204_FIELDS>Upload-Offset: <offset>\r\n<TUS_RESUMABLE>\r\n
+  let response: &Vec<u8> = &[
+    NO_CONTENT_204_FIELDS, UPLOAD_OFFSET, (offset + length).to_string().as_bytes(), &CRLF,
+    TUS_RESUMABLE, &CRLF,
+  ].concat();
+  respond(response, client, "Interrupted while responding to a PATCH request");
+
+  info!("Successfully responded to upload {:016x} of {} PATCH", id, filename);
   Result::Ok(())
 }
 
 
-fn send_file_to_client(filename: &String, stream: &TcpStream) -> Result<(), Serr> {
-  let mut amt: usize;
-  let mut file: File = match File::open(&filename) {
-    Ok(f) => f,
-    Err(_) => return Err(Serr::SERVER(format!("Could not open {} which was already fetched from datastore", &filename)))
-  };
-  let mut buf: Vec<u8>;
+/// Format seconds since the Unix epoch as an HTTP date,
+/// e.g. Thu, 01 Jan 1970 00:00:00 GMT
+pub fn http_date(secs: u64) -> String {
+  let days: u64 = secs / 86400;
+  let time: u64 = secs % 86400;
+
+  // convert days to a date of the proleptic Gregorian calendar, counting
+  // from March so leap days end the year, in eras of 400 years
+  let z: u64 = days + 719468;
+  let era: u64 = z / 146097;
+  let day_of_era: u64 = z % 146097;
+  let year_of_era: u64 = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  let day_of_year: u64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month: u64 = (5 * day_of_year + 2) / 153;  // 0 is March
+  let day: u64 = day_of_year - (153 * month + 2) / 5 + 1;
+  let month: u64 = if month < 10 { month + 2 } else { month - 10 };  // 0 is January
+  let year: u64 = year_of_era + era * 400 + u64::from(month < 2);
+
+  format!("{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+    WEEKDAYS[(days % 7) as usize], day, MONTHS[month as usize], year,
+    time / 3600, time % 3600 / 60, time % 60)
+}
 
-  loop {
-    buf = vec![0u8; MTU];
-    amt = match file.read(&mut buf) {
+
+/// The body of a request, read up to its Content-Length.
+///
+/// Reading fails if the stream ends early or times out,
+/// rather than sending a truncated file.
+pub struct Body<R: Read> {
+  reader: R,
+  remaining: u64,  // bytes of the body not read yet
+}
+
+
+impl<R: Read> Body<R> {
+  /// Read a body of the provided length from the reader.
+  pub fn new(reader: R, length: u64) -> Body<R> {
+    Body { reader, remaining: length }
+  }
+
+
+  /// Determine if the body was read in full, so the next
+  /// request on the connection starts where it ends.
+  pub fn is_done(&self) -> bool {
+    self.remaining == 0
+  }
+}
+
+
+impl<R: Read> Read for Body<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.remaining == 0 { return Ok(0); }
+
+    let max: usize = buf.len().min(self.remaining.try_into().unwrap_or(usize::MAX));
+    let amt: usize = match self.reader.read(&mut buf[..max]) {
       Ok(i) => i,
-      Err(_) => return Err(Serr::SERVER(format!("Could not read from {} which was already fetched from datastore", &filename))),
+      Err(e) if e.kind() == ErrorKind::WouldBlock => {
+        return Err(io::Error::new(ErrorKind::TimedOut, format!("stream stalled {} bytes before the end of the body", self.remaining)));
+      },
+      Err(e) => return Err(e),
     };
-
     if amt == 0 {
-      break;
+      return Err(io::Error::new(ErrorKind::UnexpectedEof, format!("stream ended {} bytes before the end of the body", self.remaining)));
     }
-    respond(&buf , stream, "Unable to sent data to client");
+
+    self.remaining -= amt as u64;
+    Ok(amt)
   }
-  Ok(())
-}
\ No newline at end of file
+}